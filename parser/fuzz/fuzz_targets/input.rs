@@ -0,0 +1,18 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use parser::command::{expand_aliases, Input};
+use parser::IgnoreOptions;
+
+fuzz_target!(|input: &str| {
+    let bot = vec!["rustbot", "triagebot"];
+    for email_replies in [false, true].iter().copied() {
+        let options = IgnoreOptions { email_replies };
+        let commands = Input::with_options(input, bot.clone(), options).count();
+        // Each command starts at a distinct mention of the bot.
+        assert!(commands <= input.matches('@').count());
+    }
+
+    let aliases = std::iter::once(("ptal".to_owned(), "ready".to_owned())).collect();
+    let expanded = expand_aliases(input, &bot, &aliases);
+    Input::new(&expanded, bot).for_each(drop);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use parser::{get_mentions_with, IgnoreOptions};
+
+fuzz_target!(|input: &str| {
+    for email_replies in [false, true].iter().copied() {
+        for mention in get_mentions_with(input, IgnoreOptions { email_replies }) {
+            let start = mention.as_ptr() as usize - input.as_ptr() as usize;
+            assert!(!mention.is_empty());
+            assert_eq!(&input[start - 1..start], "@");
+        }
+    }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use parser::token::Tokenizer;
+
+fuzz_target!(|input: &str| {
+    let mut tok = Tokenizer::new(input);
+    let mut previous = 0;
+    loop {
+        let position = tok.position();
+        assert!(previous <= position && position <= input.len());
+        assert!(input.is_char_boundary(position));
+        previous = position;
+        match tok.next_token() {
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(e) => {
+                e.to_string();
+                break;
+            }
+        }
+    }
+});
@@ -1,10 +1,14 @@
 use crate::error::Error;
-use crate::ignore_block::IgnoreBlocks;
+use crate::ignore_block::{IgnoreBlocks, IgnoreOptions};
 use crate::token::{Token, Tokenizer};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 
 pub mod assign;
 pub mod close;
 pub mod glacier;
+pub mod help;
 pub mod nominate;
 pub mod note;
 pub mod ping;
@@ -12,23 +16,177 @@ pub mod prioritize;
 pub mod relabel;
 pub mod second;
 pub mod shortcut;
+pub mod unknown;
 
 pub fn find_command_start(input: &str, bot: &str) -> Option<usize> {
     input.to_ascii_lowercase().find(&format!("@{}", bot))
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Command<'a> {
-    Relabel(Result<relabel::RelabelCommand, Error<'a>>),
-    Assign(Result<assign::AssignCommand, Error<'a>>),
-    Ping(Result<ping::PingCommand, Error<'a>>),
-    Nominate(Result<nominate::NominateCommand, Error<'a>>),
-    Prioritize(Result<prioritize::PrioritizeCommand, Error<'a>>),
-    Second(Result<second::SecondCommand, Error<'a>>),
-    Glacier(Result<glacier::GlacierCommand, Error<'a>>),
-    Shortcut(Result<shortcut::ShortcutCommand, Error<'a>>),
-    Close(Result<close::CloseCommand, Error<'a>>),
-    Note(Result<note::NoteCommand, Error<'a>>),
+/// A command the bot understands, registered by the module containing its parser.
+pub struct CommandInfo {
+    /// The name of the `Command` variant the command is parsed into.
+    pub name: &'static str,
+    /// The words which start the command.
+    pub aliases: &'static [&'static str],
+    /// The forms of the command, without the bot mention, as described in the module
+    /// documentation of its parser.
+    pub help: &'static [&'static str],
+    /// Parses the command following the bot mention, if it is this command.
+    pub parse: for<'a> fn(&Tokenizer<'a>) -> Option<(Tokenizer<'a>, Command<'a>)>,
+}
+
+/// Invokes `$callback!` with the list of commands, which is the only place where a
+/// command is registered: both the `Command` enum and the handlers of the bot are
+/// generated from it.
+///
+/// Each command module registers itself with a `COMMAND` constant, and is listed along
+/// with the type its parser produces, the `Command` variant holding it and the section
+/// of `triagebot.toml` enabling it, which is also the name of the module of the bot
+/// handling it. `help` is always available, so it isn't listed.
+#[macro_export]
+macro_rules! for_each_command {
+    ($callback:ident) => {
+        $callback! {
+            assign::AssignCommand => Assign in assign,
+            glacier::GlacierCommand => Glacier in glacier,
+            nominate::NominateCommand => Nominate in nominate,
+            ping::PingCommand => Ping in ping,
+            prioritize::PrioritizeCommand => Prioritize in prioritize,
+            relabel::RelabelCommand => Relabel in relabel,
+            second::SecondCommand => Second in major_change,
+            shortcut::ShortcutCommand => Shortcut in shortcut,
+            close::CloseCommand => Close in close,
+            note::NoteCommand => Note in note,
+        }
+    };
+}
+
+macro_rules! commands {
+    ($($module:ident::$command:ident => $variant:ident in $section:ident,)*) => {
+        #[derive(Debug, PartialEq)]
+        pub enum Command<'a> {
+            $($variant(Result<$module::$command, Error<'a>>),)*
+            Help(Result<help::HelpCommand, Error<'a>>),
+            /// The bot was addressed with a word which does not start any command.
+            Unknown(unknown::UnknownCommand),
+            /// More than one parser recognized the command.
+            Ambiguous(AmbiguousCommand),
+        }
+
+        /// The registered commands, in the order their parsers are tried.
+        pub const REGISTRY: &[CommandInfo] = &[$($module::COMMAND,)* help::COMMAND];
+
+        impl<'a> Command<'a> {
+            pub fn is_ok(&self) -> bool {
+                match self {
+                    $(Command::$variant(r) => r.is_ok(),)*
+                    Command::Help(r) => r.is_ok(),
+                    Command::Unknown(_) => false,
+                    Command::Ambiguous(_) => false,
+                }
+            }
+
+            /// The name of this variant.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Command::$variant(_) => stringify!($variant),)*
+                    Command::Help(_) => "Help",
+                    Command::Unknown(_) => "Unknown",
+                    Command::Ambiguous(_) => "Ambiguous",
+                }
+            }
+        }
+    };
+}
+
+for_each_command!(commands);
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AmbiguousCommand {
+    /// The names of the `Command` variants the input could be parsed into.
+    pub commands: Vec<&'static str>,
+}
+
+impl std::error::Error for AmbiguousCommand {}
+
+impl fmt::Display for AmbiguousCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ambiguous command, which could be any of: {}",
+            self.commands.join(", ")
+        )
+    }
+}
+
+/// Replaces the word following a mention of the bot with its expansion, if it is one
+/// of `aliases` regardless of case: given `ptal = "ready"`, `@bot PTAL` becomes
+/// `@bot ready`. The rest of the command is kept, and expansions are not expanded
+/// again. Built-in commands are never replaced, and an alias matching the word's
+/// case exactly wins over its case variants, of which the first in sorted order is
+/// used otherwise.
+pub fn expand_aliases<'a>(
+    input: &'a str,
+    bot: &[&str],
+    aliases: &HashMap<String, String>,
+) -> Cow<'a, str> {
+    if aliases.is_empty() {
+        return Cow::Borrowed(input);
+    }
+    let ignore = IgnoreBlocks::new(input);
+    let mut expanded = String::new();
+    let mut copied = 0;
+    let mut pos = 0;
+    while let Some(start) = bot
+        .iter()
+        .filter_map(|name| find_command_start(&input[pos..], name))
+        .min()
+    {
+        let start = pos + start;
+        let mut tok = Tokenizer::new(&input[start..]);
+        match tok.next_token() {
+            Ok(Some(Token::Word(mention)))
+                if bot
+                    .iter()
+                    .any(|name| mention.eq_ignore_ascii_case(&format!("@{}", name))) =>
+            {
+                pos = start + mention.len();
+            }
+            // Part of a longer word, such as another user's name.
+            _ => {
+                pos = start + 1;
+                continue;
+            }
+        }
+        let (word, end) = match tok.next_token() {
+            Ok(Some(Token::Word(word))) => (word, start + tok.position()),
+            _ => continue,
+        };
+        if is_command_word(word) {
+            continue;
+        }
+        let expansion = aliases.get(word).or_else(|| {
+            aliases
+                .iter()
+                .filter(|(alias, _)| alias.eq_ignore_ascii_case(word))
+                .min_by_key(|(alias, _)| *alias)
+                .map(|(_, expansion)| expansion)
+        });
+        let expansion = match expansion {
+            Some(expansion) if ignore.overlaps_ignore(start..end).is_none() => expansion,
+            _ => continue,
+        };
+        expanded.push_str(&input[copied..end - word.len()]);
+        expanded.push_str(expansion);
+        copied = end;
+        pos = end;
+    }
+    if copied == 0 {
+        Cow::Borrowed(input)
+    } else {
+        expanded.push_str(&input[copied..]);
+        Cow::Owned(expanded)
+    }
 }
 
 #[derive(Debug)]
@@ -61,91 +219,48 @@ where
     }
 }
 
+/// Runs every command parser on `tokenizer`, returning the commands which were
+/// recognized. More than one means that the command is ambiguous.
+fn parse_all<'a>(tokenizer: &Tokenizer<'a>) -> Vec<(Tokenizer<'a>, Command<'a>)> {
+    REGISTRY
+        .iter()
+        .filter_map(|command| (command.parse)(tokenizer))
+        .collect()
+}
+
 impl<'a> Input<'a> {
     pub fn new(input: &'a str, bot: Vec<&'a str>) -> Input<'a> {
+        Input::with_options(input, bot, IgnoreOptions::default())
+    }
+
+    /// Like `new`, additionally skipping the parts of `input` selected by `options`.
+    pub fn with_options(input: &'a str, bot: Vec<&'a str>, options: IgnoreOptions) -> Input<'a> {
         Input {
             all: input,
             parsed: 0,
-            ignore: IgnoreBlocks::new(input),
+            ignore: IgnoreBlocks::with_options(input, options),
             bot,
         }
     }
 
     fn parse_command(&mut self) -> Option<Command<'a>> {
         let mut tok = Tokenizer::new(&self.all[self.parsed..]);
-        let name_length = if let Ok(Some(Token::Word(bot_name))) = tok.next_token() {
-            assert!(self
-                .bot
-                .iter()
-                .any(|name| bot_name.eq_ignore_ascii_case(&format!("@{}", name))));
-            bot_name.len()
-        } else {
-            panic!("no bot name?")
+        let name_length = match tok.next_token() {
+            Ok(Some(Token::Word(bot_name)))
+                if self
+                    .bot
+                    .iter()
+                    .any(|name| bot_name.eq_ignore_ascii_case(&format!("@{}", name))) =>
+            {
+                bot_name.len()
+            }
+            // The mention is part of a longer word, such as another user's name.
+            _ => return None,
         };
         log::info!("identified potential command");
 
-        let mut success = vec![];
-
         let original_tokenizer = tok.clone();
-
-        success.extend(parse_single_command(
-            relabel::RelabelCommand::parse,
-            Command::Relabel,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            assign::AssignCommand::parse,
-            Command::Assign,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            note::NoteCommand::parse,
-            Command::Note,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            ping::PingCommand::parse,
-            Command::Ping,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            nominate::NominateCommand::parse,
-            Command::Nominate,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            prioritize::PrioritizeCommand::parse,
-            Command::Prioritize,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            second::SecondCommand::parse,
-            Command::Second,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            glacier::GlacierCommand::parse,
-            Command::Glacier,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            shortcut::ShortcutCommand::parse,
-            Command::Shortcut,
-            &original_tokenizer,
-        ));
-        success.extend(parse_single_command(
-            close::CloseCommand::parse,
-            Command::Close,
-            &original_tokenizer,
-        ));
-
-        if success.len() > 1 {
-            panic!(
-                "succeeded parsing {:?} to multiple commands: {:?}",
-                &self.all[self.parsed..],
-                success
-            );
-        }
+        let mut success = parse_all(&original_tokenizer);
 
         if self
             .ignore
@@ -156,7 +271,22 @@ impl<'a> Input<'a> {
             return None;
         }
 
-        let (mut tok, c) = success.pop()?;
+        if success.len() > 1 {
+            log::error!(
+                "succeeded parsing {:?} to multiple commands: {:?}",
+                &self.all[self.parsed..],
+                success
+            );
+            self.parsed += name_length;
+            return Some(Command::Ambiguous(AmbiguousCommand {
+                commands: success.iter().map(|(_, command)| command.name()).collect(),
+            }));
+        }
+
+        let (mut tok, c) = match success.pop() {
+            Some(parsed) => parsed,
+            None => return self.unknown_command(original_tokenizer),
+        };
         // if we errored out while parsing the command do not move the input forwards
         self.parsed += if c.is_ok() {
             tok.position()
@@ -165,6 +295,33 @@ impl<'a> Input<'a> {
         };
         Some(c)
     }
+
+    /// Reports the word following the bot mention as an unknown command, if the
+    /// mention starts a line and the word is likely a misspelled command: otherwise,
+    /// the bot is more likely mentioned in passing or replied to.
+    fn unknown_command(&mut self, mut tok: Tokenizer<'a>) -> Option<Command<'a>> {
+        let line_start = self.all[..self.parsed].rfind('\n').map_or(0, |i| i + 1);
+        if !self.all[line_start..self.parsed].trim().is_empty() {
+            return None;
+        }
+        let word = match tok.next_token() {
+            Ok(Some(Token::Word(word))) if !word.starts_with('@') => word,
+            _ => return None,
+        };
+        if self
+            .ignore
+            .overlaps_ignore((self.parsed)..(self.parsed + tok.position()))
+            .is_some()
+        {
+            return None;
+        }
+        let unknown = unknown::UnknownCommand::new(word, command_words());
+        if !unknown.is_likely_typo() {
+            return None;
+        }
+        self.parsed += tok.position();
+        Some(Command::Unknown(unknown))
+    }
 }
 
 impl<'a> Iterator for Input<'a> {
@@ -181,36 +338,42 @@ impl<'a> Iterator for Input<'a> {
             if let Some(command) = self.parse_command() {
                 return Some(command);
             }
-            self.parsed += self.bot.len() + 1;
+            // Skip the `@`, to look for the next mention.
+            self.parsed += 1;
         }
     }
 }
 
 impl<'a> Command<'a> {
-    pub fn is_ok(&self) -> bool {
-        match self {
-            Command::Relabel(r) => r.is_ok(),
-            Command::Assign(r) => r.is_ok(),
-            Command::Ping(r) => r.is_ok(),
-            Command::Nominate(r) => r.is_ok(),
-            Command::Prioritize(r) => r.is_ok(),
-            Command::Second(r) => r.is_ok(),
-            Command::Glacier(r) => r.is_ok(),
-            Command::Shortcut(r) => r.is_ok(),
-            Command::Close(r) => r.is_ok(),
-            Command::Note(r) => r.is_ok(),
-        }
-    }
-
     pub fn is_err(&self) -> bool {
         !self.is_ok()
     }
 }
 
+/// The words which start a command.
+fn command_words() -> impl Iterator<Item = &'static str> {
+    REGISTRY
+        .iter()
+        .flat_map(|command| command.aliases.iter().copied())
+}
+
+/// Whether `word` starts a built-in command, regardless of case.
+pub fn is_command_word(word: &str) -> bool {
+    command_words().any(|command| command.eq_ignore_ascii_case(word))
+}
+
+/// The forms of the command parsed into the `Command` variant named `variant`, as
+/// described in the module documentation of its parser, without the bot mention.
+pub fn usage(variant: &str) -> &'static [&'static str] {
+    REGISTRY
+        .iter()
+        .find(|command| command.name == variant)
+        .map_or(&[], |command| command.help)
+}
+
 #[test]
 fn errors_outside_command_are_fine() {
-    let input =
-        "haha\" unterminated quotes @bot labels +bug. Terminating after the command";
+    let input = "haha\" unterminated quotes @bot labels +bug. Terminating after the command";
     let mut input = Input::new(input, vec!["bot"]);
     assert!(input.next().unwrap().is_ok());
 }
@@ -278,3 +441,227 @@ fn multiname() {
     assert!(input.next().unwrap().is_ok());
     assert!(input.next().is_none());
 }
+
+#[test]
+fn unknown_command() {
+    let input = "@bot lable +bug\n@bot label +bug";
+    let mut input = Input::new(input, vec!["bot"]);
+    assert_eq!(
+        input.next(),
+        Some(Command::Unknown(unknown::UnknownCommand {
+            word: "lable".to_owned(),
+            suggestion: Some("label"),
+        }))
+    );
+    assert!(input.next().unwrap().is_ok());
+    assert!(input.next().is_none());
+}
+
+#[test]
+fn unknown_command_ignoring_case() {
+    let input = "@bot LABLE +bug";
+    let mut input = Input::new(input, vec!["bot"]);
+    assert_eq!(
+        input.next(),
+        Some(Command::Unknown(unknown::UnknownCommand {
+            word: "LABLE".to_owned(),
+            suggestion: Some("label"),
+        }))
+    );
+}
+
+#[test]
+fn unknown_command_in_conversation() {
+    for input in &[
+        "@bot thanks!",
+        "@bot no",
+        "@bot nope, that's intended",
+        "@bot r? @ferris",
+        "@bot hey, could you have a look?",
+        "@bot frobnicate the widgets",
+    ] {
+        assert_eq!(Input::new(input, vec!["bot"]).next(), None, "{:?}", input);
+    }
+}
+
+#[test]
+fn unknown_command_in_passing() {
+    let input = "thanks @bot for the help";
+    let mut input = Input::new(input, vec!["bot"]);
+    assert!(input.next().is_none());
+}
+
+#[test]
+fn unknown_command_in_code() {
+    let input = "```\n@bot lable +bug\n```";
+    let mut input = Input::new(input, vec!["bot"]);
+    assert!(input.next().is_none());
+}
+
+/// Sample commands, each of which must be recognized by exactly one parser.
+#[cfg(test)]
+const CORPUS: &[&str] = &[
+    "label +bug",
+    "labels: +T-compiler -T-libs",
+    "modify labels to +A-diagnostics and -A-lint.",
+    "claim",
+    "release-assignment",
+    "assign @ferris",
+    "ping compiler",
+    "beta-nominate compiler",
+    "nominate libs",
+    "beta-accept",
+    "beta-approve",
+    "prioritize",
+    "second",
+    "seconded",
+    "glacier \"https://play.rust-lang.org/\"",
+    "ready",
+    "review",
+    "author",
+    "blocked",
+    "close",
+    "note summary",
+    "note remove summary",
+    "note \"Some title\"",
+    "help",
+];
+
+#[test]
+fn parsers_are_unambiguous() {
+    for sample in CORPUS {
+        let parsed = parse_all(&Tokenizer::new(sample));
+        let names = parsed
+            .iter()
+            .map(|(_, command)| command.name())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names.len(),
+            1,
+            "{:?} must be recognized by exactly one parser, but is by {:?}",
+            sample,
+            names
+        );
+    }
+}
+
+#[test]
+fn registry_is_consistent() {
+    for (i, command) in REGISTRY.iter().enumerate() {
+        assert!(
+            REGISTRY[..i].iter().all(|other| other.name != command.name),
+            "{} is registered twice",
+            command.name
+        );
+        for alias in command.aliases {
+            let parsed = parse_all(&Tokenizer::new(alias))
+                .into_iter()
+                .map(|(_, parsed)| parsed.name())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                parsed,
+                [command.name],
+                "{:?} is not parsed as expected",
+                alias
+            );
+        }
+    }
+}
+
+#[test]
+fn aliases() {
+    let aliases = [("ptal", "ready"), ("needs-design", "label +S-needs-design")]
+        .iter()
+        .map(|&(alias, expansion)| (alias.to_owned(), expansion.to_owned()))
+        .collect::<HashMap<_, _>>();
+    let expand = |input| expand_aliases(input, &["bot"], &aliases);
+
+    assert_eq!(expand("@bot ptal"), "@bot ready");
+    assert_eq!(
+        expand("Thanks!\n@Bot needs-design.\n@bot label +bug"),
+        "Thanks!\n@Bot label +S-needs-design.\n@bot label +bug"
+    );
+    assert_eq!(expand("`@bot ptal`"), "`@bot ptal`");
+    assert_eq!(expand("@botanist ptal"), "@botanist ptal");
+    assert_eq!(expand("@bot ptally"), "@bot ptally");
+    assert_eq!(expand("@bot PTAL"), "@bot ready");
+
+    let input = expand("@bot needs-design. Thanks!");
+    let mut input = Input::new(&input, vec!["bot"]);
+    match input.next() {
+        Some(Command::Relabel(Ok(relabel::RelabelCommand(deltas)))) => {
+            assert_eq!(deltas.len(), 1);
+            assert_eq!(**deltas[0].label(), "S-needs-design");
+        }
+        command => panic!("unexpected {:?}", command),
+    }
+
+    let aliases = [
+        ("close", "label +S-blocked"),
+        ("Ready", "label +S-waiting-on-review"),
+        ("PTAL", "label +S-waiting-on-review"),
+        ("ptal", "ready"),
+    ]
+    .iter()
+    .map(|&(alias, expansion)| (alias.to_owned(), expansion.to_owned()))
+    .collect::<HashMap<_, _>>();
+    let expand = |input| expand_aliases(input, &["bot"], &aliases);
+    assert_eq!(expand("@bot close"), "@bot close");
+    assert_eq!(expand("@bot ready"), "@bot ready");
+    assert_eq!(expand("@bot ptal"), "@bot ready");
+    assert_eq!(expand("@bot PTAL"), "@bot label +S-waiting-on-review");
+    assert_eq!(expand("@bot Ptal"), "@bot label +S-waiting-on-review");
+}
+
+#[test]
+fn command_in_html_comment() {
+    let input = "<!--\nIf this is ready, comment `@bot ready`:\n@bot ready\n-->";
+    let mut input = Input::new(input, vec!["bot"]);
+    assert!(input.next().is_none());
+}
+
+#[test]
+fn command_in_details() {
+    let input = "<details>\n<summary>Log</summary>\n\n@bot ready\n</details>\n@bot author";
+    let mut input = Input::new(input, vec!["bot"]);
+    assert_eq!(
+        input.next(),
+        Some(Command::Shortcut(Ok(shortcut::ShortcutCommand::Author)))
+    );
+    assert!(input.next().is_none());
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn parsing_stays_in_bounds(input in crate::strategy::comment()) {
+        let mut parser = Input::new(&input, crate::strategy::BOT.to_vec());
+        let mut commands = 0;
+        while parser.next().is_some() {
+            commands += 1;
+            // Each command starts at a distinct mention of the bot.
+            proptest::prop_assert!(commands <= input.matches('@').count());
+            proptest::prop_assert!(parser.parsed <= input.len());
+            proptest::prop_assert!(input.is_char_boundary(parser.parsed));
+        }
+    }
+
+    #[test]
+    fn alias_expansion_keeps_text(input in crate::strategy::comment()) {
+        let aliases = std::iter::once(("ptal".to_owned(), "ready".to_owned())).collect();
+        let expanded = expand_aliases(&input, crate::strategy::BOT, &aliases);
+        // Expanding only turns some occurrences of `ptal`, in any case, into `ready`.
+        proptest::prop_assert_eq!(
+            expanded.to_ascii_lowercase().replace("ready", "ptal"),
+            input.to_ascii_lowercase().replace("ready", "ptal")
+        );
+    }
+}
+
+#[test]
+fn mention_of_other_user() {
+    let input = "@botanist label +bug\n@bot label +bug";
+    let mut input = Input::new(input, vec!["bot"]);
+    assert!(input.next().unwrap().is_ok());
+    assert!(input.next().is_none());
+}
@@ -8,10 +8,18 @@
 //! Command: `@bot claim`, `@bot release-assignment`, or `@bot assign @user`.
 //! ```
 
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 use std::fmt;
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Assign",
+    aliases: &["claim", "release-assignment", "assign"],
+    help: &["claim", "release-assignment", "assign @<user>"],
+    parse: |input| parse_single_command(AssignCommand::parse, Command::Assign, input),
+};
+
 #[derive(PartialEq, Eq, Debug)]
 pub enum AssignCommand {
     Own,
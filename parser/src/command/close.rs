@@ -1,6 +1,14 @@
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Close",
+    aliases: &["close"],
+    help: &["close"],
+    parse: |input| parse_single_command(CloseCommand::parse, Command::Close, input),
+};
+
 #[derive(PartialEq, Eq, Debug)]
 pub struct CloseCommand;
 
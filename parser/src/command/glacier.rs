@@ -10,10 +10,18 @@
 //! <code-source>: any URL that resolves to plain-text Rust code
 //! ```
 
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 use std::fmt;
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Glacier",
+    aliases: &["glacier"],
+    help: &["glacier <code-source>"],
+    parse: |input| parse_single_command(GlacierCommand::parse, Command::Glacier, input),
+};
+
 #[derive(PartialEq, Eq, Debug)]
 pub struct GlacierCommand {
     pub source: String,
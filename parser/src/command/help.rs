@@ -0,0 +1,45 @@
+//! The help command parser.
+//!
+//! The grammar is as follows:
+//!
+//! ```text
+//! Command: `@bot help`.
+//! ```
+
+use super::{parse_single_command, Command, CommandInfo};
+use crate::error::Error;
+use crate::token::{Token, Tokenizer};
+
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Help",
+    aliases: &["help"],
+    help: &["help"],
+    parse: |input| parse_single_command(HelpCommand::parse, Command::Help, input),
+};
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct HelpCommand;
+
+impl HelpCommand {
+    pub fn parse<'a>(input: &mut Tokenizer<'a>) -> Result<Option<Self>, Error<'a>> {
+        if let Some(Token::Word("help")) = input.peek_token()? {
+            input.next_token()?;
+            Ok(Some(Self))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> Result<Option<HelpCommand>, Error<'_>> {
+    let mut toks = Tokenizer::new(input);
+    HelpCommand::parse(&mut toks)
+}
+
+#[test]
+fn help() {
+    assert_eq!(parse("help"), Ok(Some(HelpCommand)));
+    assert_eq!(parse("help."), Ok(Some(HelpCommand)));
+    assert_eq!(parse("helpful"), Ok(None));
+}
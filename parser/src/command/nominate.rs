@@ -15,10 +15,23 @@
 //! descriptions of what to do targeted at each team, rather than a general
 //! summary.
 
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 use std::fmt;
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Nominate",
+    aliases: &["beta-nominate", "nominate", "beta-accept", "beta-approve"],
+    help: &[
+        "beta-nominate <team>",
+        "nominate <team>",
+        "beta-accept",
+        "beta-approve",
+    ],
+    parse: |input| parse_single_command(NominateCommand::parse, Command::Nominate, input),
+};
+
 #[derive(PartialEq, Eq, Debug)]
 pub struct NominateCommand {
     pub team: String,
@@ -1,7 +1,15 @@
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 use std::fmt;
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Note",
+    aliases: &["note"],
+    help: &["note <title>", "note remove <title>"],
+    parse: |input| parse_single_command(NoteCommand::parse, Command::Note, input),
+};
+
 #[derive(PartialEq, Eq, Debug)]
 pub enum NoteCommand {
     Summary { title: String },
@@ -8,10 +8,18 @@
 //! Command: `@bot ping <team>`.
 //! ```
 
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 use std::fmt;
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Ping",
+    aliases: &["ping"],
+    help: &["ping <team>"],
+    parse: |input| parse_single_command(PingCommand::parse, Command::Ping, input),
+};
+
 #[derive(PartialEq, Eq, Debug)]
 pub struct PingCommand {
     pub team: String,
@@ -1,9 +1,17 @@
 #[derive(PartialEq, Eq, Debug)]
 pub struct PrioritizeCommand;
 
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Prioritize",
+    aliases: &["prioritize"],
+    help: &["prioritize"],
+    parse: |input| parse_single_command(PrioritizeCommand::parse, Command::Prioritize, input),
+};
+
 impl PrioritizeCommand {
     pub fn parse<'a>(input: &mut Tokenizer<'a>) -> Result<Option<Self>, Error<'a>> {
         if let Some(Token::Word("prioritize")) = input.peek_token()? {
@@ -27,12 +27,20 @@
 //! <label>: \S+
 //! ```
 
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 #[cfg(test)]
 use std::error::Error as _;
 use std::fmt;
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Relabel",
+    aliases: &["label", "labels"],
+    help: &["label +<label> -<label>"],
+    parse: |input| parse_single_command(RelabelCommand::parse, Command::Relabel, input),
+};
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct RelabelCommand(pub Vec<LabelDelta>);
 
@@ -1,6 +1,14 @@
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Second",
+    aliases: &["second", "seconded"],
+    help: &["second", "seconded"],
+    parse: |input| parse_single_command(SecondCommand::parse, Command::Second, input),
+};
+
 #[derive(PartialEq, Eq, Debug)]
 pub struct SecondCommand;
 
@@ -5,14 +5,22 @@
 //! The grammar is as follows:
 //!
 //! ```text
-//! Command: `@bot ready`/`@bot review`, or `@bot author`.
+//! Command: `@bot ready`/`@bot review`, `@bot author`, or `@bot blocked`.
 //! ```
 
+use super::{parse_single_command, Command, CommandInfo};
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 use std::collections::HashMap;
 use std::fmt;
 
+pub const COMMAND: CommandInfo = CommandInfo {
+    name: "Shortcut",
+    aliases: &["ready", "review", "author", "blocked"],
+    help: &["ready", "review", "author", "blocked"],
+    parse: |input| parse_single_command(ShortcutCommand::parse, Command::Shortcut, input),
+};
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum ShortcutCommand {
     Ready,
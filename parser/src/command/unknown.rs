@@ -0,0 +1,111 @@
+//! Commands which are not recognized.
+//!
+//! When the bot is addressed at the start of a line with a word which does not start
+//! any command, but is close to one by edit distance regardless of case, that command
+//! is suggested. Other words are not reported, as they are usually replies to the bot.
+
+use std::fmt;
+
+/// Words addressing the bot which are not commands, although some are close to one.
+const CONVERSATION: &[&str] = &[
+    "hello", "hey", "hi", "no", "nope", "ok", "okay", "please", "thank", "thanks", "thx", "yes",
+];
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct UnknownCommand {
+    pub word: String,
+    pub suggestion: Option<&'static str>,
+}
+
+impl UnknownCommand {
+    pub fn new(word: &str, commands: impl Iterator<Item = &'static str>) -> Self {
+        // Allow for a typo in short words, two in longer ones. Changing more of a short
+        // word makes it another word rather than a misspelling.
+        let max_distance = if word.chars().count() <= 4 { 1 } else { 2 };
+        let lowercase = word.to_lowercase();
+        let suggestion = commands
+            .map(|command| (edit_distance(&lowercase, command), command))
+            .filter(|&(distance, _)| distance <= max_distance)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, command)| command);
+        UnknownCommand {
+            word: word.to_owned(),
+            suggestion,
+        }
+    }
+
+    /// Whether the word was likely meant as a command, being close to one and not a
+    /// common word of conversation.
+    pub fn is_likely_typo(&self) -> bool {
+        self.suggestion.is_some()
+            && !CONVERSATION
+                .iter()
+                .any(|word| self.word.eq_ignore_ascii_case(word))
+    }
+}
+
+impl fmt::Display for UnknownCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown command `{}`", self.word)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, "; did you mean `{}`?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + if a == b { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[test]
+fn distance() {
+    assert_eq!(edit_distance("label", "label"), 0);
+    assert_eq!(edit_distance("lable", "label"), 2);
+    assert_eq!(edit_distance("clam", "claim"), 1);
+    assert_eq!(edit_distance("", "close"), 5);
+}
+
+#[test]
+fn suggestion() {
+    let commands = || vec!["label", "claim", "close", "ready"].into_iter();
+    assert_eq!(
+        UnknownCommand::new("lable", commands()).suggestion,
+        Some("label")
+    );
+    assert_eq!(
+        UnknownCommand::new("redy", commands()).suggestion,
+        Some("ready")
+    );
+    assert_eq!(UnknownCommand::new("thanks", commands()).suggestion, None);
+    // Short words are only a typo away from a command.
+    let commands = || vec!["label", "note", "close", "ready"].into_iter();
+    assert_eq!(UnknownCommand::new("no", commands()).suggestion, None);
+    assert_eq!(UnknownCommand::new("nit", commands()).suggestion, None);
+    assert_eq!(
+        UnknownCommand::new("nte", commands()).suggestion,
+        Some("note")
+    );
+    assert!(!UnknownCommand::new("nope", commands()).is_likely_typo());
+    assert_eq!(
+        UnknownCommand::new("Redy", commands()).suggestion,
+        Some("ready")
+    );
+    assert!(UnknownCommand::new("lable", commands()).is_likely_typo());
+    assert!(!UnknownCommand::new("Hey", vec!["help"].into_iter()).is_likely_typo());
+    assert_eq!(
+        UnknownCommand::new("lable", commands()).to_string(),
+        "unknown command `lable`; did you mean `label`?"
+    );
+}
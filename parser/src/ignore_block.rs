@@ -1,6 +1,15 @@
 use pulldown_cmark::{Event, Parser, Tag};
 use std::ops::Range;
 
+/// Which parts of a text are ignored, besides code, block quotes, HTML comments,
+/// `<details>` elements and the sections written by the bot.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IgnoreOptions {
+    /// Ignore the messages quoted in a reply sent by email, along with the
+    /// `On <date>, <someone> wrote:` lines introducing them.
+    pub email_replies: bool,
+}
+
 #[derive(Debug)]
 pub struct IgnoreBlocks {
     ignore: Vec<Range<usize>>,
@@ -8,6 +17,10 @@ pub struct IgnoreBlocks {
 
 impl IgnoreBlocks {
     pub fn new(s: &str) -> IgnoreBlocks {
+        IgnoreBlocks::with_options(s, IgnoreOptions::default())
+    }
+
+    pub fn with_options(s: &str, options: IgnoreOptions) -> IgnoreBlocks {
         let mut ignore = Vec::new();
         let mut parser = Parser::new(s).into_offset_iter();
         while let Some((event, range)) = parser.next() {
@@ -35,10 +48,44 @@ impl IgnoreBlocks {
                 }
             } else if let Event::Code(_) = event {
                 ignore.push(range);
+            } else if let Event::Html(html) = event {
+                if !html.trim_start().starts_with("<!--") {
+                    continue;
+                }
+                // Comments in HTML blocks are split into one event per line. The
+                // comment ends with `-->`, rather than with the line.
+                let start = range.start;
+                let mut end = html.find("-->").map(|i| range.start + i + 3);
+                let mut last = range.end;
+                while end.is_none() {
+                    match parser.next() {
+                        Some((Event::Html(html), range)) => {
+                            end = html.find("-->").map(|i| range.start + i + 3);
+                            last = range.end;
+                        }
+                        _ => break,
+                    }
+                }
+                ignore.push(start..end.unwrap_or(last));
             }
         }
+        ignore.extend(bot_sections(s));
+        let details = details(s, &ignore);
+        ignore.extend(details);
+        if options.email_replies {
+            ignore.extend(email_replies(s));
+        }
 
-        IgnoreBlocks { ignore }
+        // Sections written by the bot contain HTML comments, which are merged into them.
+        ignore.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for range in ignore {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        IgnoreBlocks { ignore: merged }
     }
 
     pub fn overlaps_ignore(&self, region: Range<usize>) -> Option<Range<usize>> {
@@ -52,6 +99,128 @@ impl IgnoreBlocks {
     }
 }
 
+/// The sections written by the bot, from a `<!-- TRIAGEBOT_..._START -->` marker to
+/// the matching `<!-- TRIAGEBOT_..._END -->`, or the end of the text.
+fn bot_sections(s: &str) -> Vec<Range<usize>> {
+    let mut sections = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = s[pos..].find("<!-- TRIAGEBOT_").map(|i| pos + i) {
+        let marker_end = s[start..].find("-->").map_or(s.len(), |i| start + i + 3);
+        let marker = s[start..marker_end].trim_end_matches("-->").trim_end();
+        pos = marker_end;
+        if !marker.ends_with("START") {
+            continue;
+        }
+        let end_marker = format!("{}END -->", marker.trim_end_matches("START"));
+        let end = s[pos..]
+            .find(&end_marker)
+            .map_or(s.len(), |i| pos + i + end_marker.len());
+        sections.push(start..end);
+        pos = end;
+    }
+    sections
+}
+
+/// The `<details>` elements, whose content is usually pasted from elsewhere (such as
+/// logs or other comments), up to the matching `</details>` or the end of the text.
+/// Tags in the `ignored` parts of the text, such as code, don't count.
+fn details(s: &str, ignored: &[Range<usize>]) -> Vec<Range<usize>> {
+    const OPEN: &str = "<details";
+    const CLOSE: &str = "</details>";
+    // Lowercasing ASCII keeps the offsets.
+    let lower = s.to_ascii_lowercase();
+    let counts = |pos: usize| !ignored.iter().any(|range| range.contains(&pos));
+    let mut elements = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find(OPEN).map(|i| pos + i) {
+        pos = start + OPEN.len();
+        if !counts(start) {
+            continue;
+        }
+        let mut depth = 1;
+        let end = loop {
+            let open = lower[pos..].find(OPEN).map(|i| pos + i);
+            match (open, lower[pos..].find(CLOSE).map(|i| pos + i)) {
+                (Some(open), Some(close)) if open < close => {
+                    pos = open + OPEN.len();
+                    if counts(open) {
+                        depth += 1;
+                    }
+                }
+                (_, Some(close)) => {
+                    pos = close + CLOSE.len();
+                    if counts(close) {
+                        depth -= 1;
+                        if depth == 0 {
+                            break pos;
+                        }
+                    }
+                }
+                (_, None) => break s.len(),
+            }
+        };
+        elements.push(start..end);
+        pos = end;
+    }
+    elements
+}
+
+/// The messages quoted in a reply sent by email: the `On <date>, <someone> wrote:`
+/// line introducing each (which mail clients may wrap), and the quoted lines, starting
+/// with `>`, which follow it. The introduction must contain a date or an email address,
+/// so that a sentence such as "On the other hand, @someone wrote:" isn't taken for one.
+fn email_replies(s: &str) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in s.split_inclusive('\n') {
+        lines.push((offset, line));
+        offset += line.len();
+    }
+    let end_of = |i: usize| lines[i].0 + lines[i].1.len();
+
+    let mut replies = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let (start, line) = lines[i];
+        let intro_end = (i..lines.len().min(i + 2))
+            .find(|&j| lines[j].1.trim_end().ends_with("wrote:"))
+            .filter(|&j| line.starts_with("On ") && is_email_intro(&s[start..end_of(j)]));
+        let intro_end = match intro_end {
+            Some(j) => j,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        let mut quote = intro_end + 1;
+        while quote < lines.len() && lines[quote].1.trim().is_empty() {
+            quote += 1;
+        }
+        let mut end = quote;
+        while end < lines.len() && lines[end].1.trim_start().starts_with('>') {
+            end += 1;
+        }
+        if end == quote {
+            i += 1;
+            continue;
+        }
+        replies.push(start..end_of(end - 1));
+        i = end;
+    }
+    replies
+}
+
+fn is_email_intro(intro: &str) -> bool {
+    let is_address = |word: &str| {
+        let word = word.trim_matches(|c: char| "<>(),".contains(c));
+        matches!(
+            word.split_once('@'),
+            Some((user, domain)) if !user.is_empty() && domain.contains('.')
+        )
+    };
+    intro.chars().any(|c| c.is_ascii_digit()) || intro.split_whitespace().any(is_address)
+}
+
 #[cfg(test)]
 #[derive(Debug, PartialEq, Eq)]
 enum Ignore<'a> {
@@ -61,8 +230,13 @@ enum Ignore<'a> {
 
 #[cfg(test)]
 fn bodies(s: &str) -> Vec<Ignore<'_>> {
+    bodies_with(s, IgnoreOptions::default())
+}
+
+#[cfg(test)]
+fn bodies_with(s: &str, options: IgnoreOptions) -> Vec<Ignore<'_>> {
     let mut bodies = Vec::new();
-    let cbs = IgnoreBlocks::new(s);
+    let cbs = IgnoreBlocks::with_options(s, options);
     let mut previous = 0..0;
     for range in &cbs.ignore {
         let range = range.clone();
@@ -239,3 +413,186 @@ fn cbs_11() {
         ],
     );
 }
+
+#[test]
+fn cbs_12() {
+    assert_eq!(
+        bodies("a <!-- @bot label +bug --> b"),
+        [
+            Ignore::No("a "),
+            Ignore::Yes("<!-- @bot label +bug -->"),
+            Ignore::No(" b")
+        ],
+    );
+}
+
+#[test]
+fn cbs_13() {
+    assert_eq!(
+        bodies(
+            "
+<!--
+@bot label +bug
+-->
+after
+"
+        ),
+        [
+            Ignore::No("\n"),
+            Ignore::Yes("<!--\n@bot label +bug\n-->"),
+            Ignore::No("\nafter\n")
+        ],
+    );
+}
+
+#[test]
+fn cbs_14() {
+    assert_eq!(
+        bodies(
+            "Description
+
+<!-- TRIAGEBOT_START -->
+
+<!-- TRIAGEBOT_SUMMARY_START -->
+- [\"note\" by @user](link)
+<!-- TRIAGEBOT_SUMMARY_END -->
+<!-- TRIAGEBOT_END -->
+"
+        ),
+        [
+            Ignore::No("Description\n\n"),
+            Ignore::Yes(
+                "<!-- TRIAGEBOT_START -->\n\n\
+                 <!-- TRIAGEBOT_SUMMARY_START -->\n\
+                 - [\"note\" by @user](link)\n\
+                 <!-- TRIAGEBOT_SUMMARY_END -->\n\
+                 <!-- TRIAGEBOT_END -->"
+            ),
+            Ignore::No("\n")
+        ],
+    );
+}
+
+#[test]
+fn cbs_15() {
+    let reply = "@bot ready
+
+On Mon, Jan 1, 2024 at 10:00 AM Some One <
+notifications@github.com> wrote:
+
+> @bot author
+> Reply to this email directly.
+
+@bot blocked
+";
+    assert_eq!(
+        bodies(reply),
+        [
+            Ignore::No(
+                "@bot ready\n\n\
+                 On Mon, Jan 1, 2024 at 10:00 AM Some One <\n\
+                 notifications@github.com> wrote:\n\n"
+            ),
+            Ignore::Yes("> @bot author\n> Reply to this email directly.\n"),
+            Ignore::No("\n@bot blocked\n"),
+        ],
+    );
+    assert_eq!(
+        bodies_with(
+            reply,
+            IgnoreOptions {
+                email_replies: true
+            }
+        ),
+        [
+            Ignore::No("@bot ready\n\n"),
+            Ignore::Yes(
+                "On Mon, Jan 1, 2024 at 10:00 AM Some One <\n\
+                 notifications@github.com> wrote:\n\n\
+                 > @bot author\n> Reply to this email directly.\n"
+            ),
+            Ignore::No("\n@bot blocked\n"),
+        ],
+    );
+}
+
+#[test]
+fn cbs_16() {
+    let options = IgnoreOptions {
+        email_replies: true,
+    };
+    // Neither a date nor an address.
+    assert_eq!(
+        bodies_with(
+            "On the other hand, @a wrote:\n> quote\n\n@bot ready",
+            options
+        ),
+        [
+            Ignore::No("On the other hand, @a wrote:\n"),
+            Ignore::Yes("> quote\n"),
+            Ignore::No("\n@bot ready"),
+        ],
+    );
+    // Nothing quoted.
+    assert_eq!(
+        bodies_with("On Monday at 10:00, @a wrote:\n@bot ready", options),
+        [],
+    );
+}
+
+#[test]
+fn cbs_17() {
+    assert_eq!(
+        bodies(
+            "<details>
+<summary>Backtrace</summary>
+
+```
+@bot label +bug
+</details>
+```
+</details>
+
+<details><summary>Nested</summary>
+<DETAILS>@bot ready</DETAILS>
+@bot author
+</details>
+@bot blocked
+"
+        ),
+        [
+            Ignore::Yes(
+                "<details>\n<summary>Backtrace</summary>\n\n\
+                 ```\n@bot label +bug\n</details>\n```\n</details>"
+            ),
+            Ignore::No("\n\n"),
+            Ignore::Yes(
+                "<details><summary>Nested</summary>\n\
+                 <DETAILS>@bot ready</DETAILS>\n@bot author\n</details>"
+            ),
+            Ignore::No("\n@bot blocked\n"),
+        ],
+    );
+    assert_eq!(
+        bodies("`<details>` @bot ready"),
+        [Ignore::Yes("`<details>`"), Ignore::No(" @bot ready")],
+    );
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn ranges_in_bounds(input in crate::strategy::comment(), email_replies: bool) {
+        let blocks = IgnoreBlocks::with_options(&input, IgnoreOptions { email_replies });
+        let mut previous: Option<&Range<usize>> = None;
+        for range in &blocks.ignore {
+            proptest::prop_assert!(range.start <= range.end && range.end <= input.len());
+            proptest::prop_assert!(input.is_char_boundary(range.start));
+            proptest::prop_assert!(input.is_char_boundary(range.end));
+            if let Some(previous) = previous {
+                proptest::prop_assert!(previous.end < range.start, "{:?}", blocks.ignore);
+            }
+            previous = Some(range);
+        }
+    }
+}
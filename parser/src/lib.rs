@@ -2,6 +2,9 @@ pub mod command;
 pub mod error;
 mod ignore_block;
 mod mentions;
-mod token;
+#[cfg(test)]
+mod strategy;
+pub mod token;
 
-pub use mentions::get_mentions;
+pub use ignore_block::IgnoreOptions;
+pub use mentions::{get_mentions, get_mentions_with};
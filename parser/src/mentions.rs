@@ -1,12 +1,21 @@
+use crate::ignore_block::{IgnoreBlocks, IgnoreOptions};
+
 /// This provides a list of usernames or teams that were pinged in the text
 /// provided.
 ///
 /// It will appropriately skip mentions just like GitHub, i.e., mentions inside
-/// code blocks will be ignored.
+/// code blocks will be ignored. Mentions in HTML comments, such as those of pull
+/// request templates, and in the sections written by the bot are ignored as well.
 ///
 /// Note that the `@` is skipped in the final output.
 pub fn get_mentions(input: &str) -> Vec<&str> {
-    let ignore_regions = crate::ignore_block::IgnoreBlocks::new(input);
+    get_mentions_with(input, IgnoreOptions::default())
+}
+
+/// Like `get_mentions`, additionally skipping the parts of `input` selected by
+/// `options`.
+pub fn get_mentions_with(input: &str, options: IgnoreOptions) -> Vec<&str> {
+    let ignore_regions = IgnoreBlocks::with_options(input, options);
 
     let mut mentions = Vec::new();
     for (idx, _) in input.match_indices('@') {
@@ -84,3 +93,24 @@ fn no_panic_lone() {
 fn no_email() {
     assert_eq!(get_mentions("user@example.com"), Vec::<&str>::new());
 }
+
+#[test]
+fn mentions_in_html_comments_ignored() {
+    assert_eq!(
+        get_mentions("<!-- cc @rust-lang/libs -->\n@user"),
+        vec!["user"]
+    );
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn mentions_follow_an_at(input in crate::strategy::comment(), email_replies: bool) {
+        for mention in get_mentions_with(&input, IgnoreOptions { email_replies }) {
+            let start = mention.as_ptr() as usize - input.as_ptr() as usize;
+            proptest::prop_assert!(!mention.is_empty());
+            proptest::prop_assert!(start >= 1 && start + mention.len() <= input.len());
+            proptest::prop_assert_eq!(&input[start - 1..start], "@");
+        }
+    }
+}
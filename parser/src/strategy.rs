@@ -0,0 +1,102 @@
+//! Generation of comment text for the property tests.
+//!
+//! Comments are built from pieces which are meaningful to the parser (bot mentions,
+//! command words, Markdown and HTML delimiters) mixed with arbitrary text, or from the
+//! synthetic seed comments of the fuzz targets with such pieces spliced in.
+
+use proptest::prelude::*;
+
+/// The bot names used when parsing generated comments.
+pub(crate) const BOT: &[&str] = &["bot", "triagebot"];
+
+const PIECES: &[&str] = &[
+    "@bot",
+    "@Bot",
+    "@triagebot",
+    "@botanist",
+    "@rustbot",
+    "@user",
+    "@rust-lang/libs",
+    "label",
+    "labels:",
+    "+bug",
+    "-T-libs",
+    "to",
+    "ready",
+    "ptal",
+    "ping",
+    "assign",
+    "note",
+    "remove",
+    "glacier",
+    "help",
+    " ",
+    "  ",
+    "\n",
+    "\n\n",
+    "\r\n",
+    "\t",
+    "`",
+    "```",
+    "    ",
+    "> ",
+    "<!--",
+    "-->",
+    "<!-- TRIAGEBOT_START -->",
+    "<!-- TRIAGEBOT_END -->",
+    "<!-- TRIAGEBOT_SUMMARY_START -->",
+    "<details>",
+    "On Mon, Jan 1, 2024 at 10:00 AM Someone <",
+    "notifications@github.com> wrote:",
+    "\"",
+    "r#\"",
+    ",",
+    ".",
+    ":",
+    "?",
+    "(",
+    ")",
+    "é",
+    "ü",
+    "🦀",
+    "\u{200b}",
+];
+
+const SEEDS: &[&str] = &[
+    include_str!("../fuzz/seeds/synthetic-assignment.md"),
+    include_str!("../fuzz/seeds/synthetic-bot-sections.md"),
+    include_str!("../fuzz/seeds/synthetic-email-reply.md"),
+    include_str!("../fuzz/seeds/synthetic-ice-report.md"),
+    include_str!("../fuzz/seeds/synthetic-misc.md"),
+    include_str!("../fuzz/seeds/synthetic-nomination.md"),
+    include_str!("../fuzz/seeds/synthetic-pr-template.md"),
+    include_str!("../fuzz/seeds/synthetic-triage.md"),
+];
+
+fn piece() -> impl Strategy<Value = String> {
+    prop_oneof![
+        3 => prop::sample::select(PIECES).prop_map(str::to_owned),
+        1 => "\\PC{0,8}",
+    ]
+}
+
+/// Text resembling a comment on GitHub.
+pub(crate) fn comment() -> impl Strategy<Value = String> {
+    let pieces = prop::collection::vec(piece(), 0..32).prop_map(|pieces| pieces.concat());
+    let spliced = (
+        prop::sample::select(SEEDS),
+        prop::collection::vec((any::<prop::sample::Index>(), piece()), 0..4),
+    )
+        .prop_map(|(seed, insertions)| {
+            let mut comment = seed.to_owned();
+            for (index, piece) in insertions {
+                let mut at = index.index(comment.len() + 1);
+                while !comment.is_char_boundary(at) {
+                    at -= 1;
+                }
+                comment.insert_str(at, &piece);
+            }
+            comment
+        });
+    prop_oneof![pieces, spliced]
+}
@@ -349,3 +349,29 @@ fn tokenize_raw_string_prohibit_1() {
         (18, ErrorKind::QuoteInWord)
     );
 }
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn positions_are_char_boundaries(input in crate::strategy::comment()) {
+        let mut tok = Tokenizer::new(&input);
+        let mut previous = 0;
+        // Every token but the final end of line consumes at least one character.
+        for _ in 0..=input.chars().count() + 1 {
+            let position = tok.position();
+            proptest::prop_assert!(previous <= position && position <= input.len());
+            proptest::prop_assert!(input.is_char_boundary(position));
+            previous = position;
+            match tok.next_token() {
+                Ok(Some(_)) => {}
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    proptest::prop_assert!(input.is_char_boundary(e.position()));
+                    e.to_string();
+                    return Ok(());
+                }
+            }
+        }
+        proptest::prop_assert!(false, "tokenizer did not reach the end of the input");
+    }
+}
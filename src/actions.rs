@@ -90,6 +90,7 @@ impl<'a> Action for Step<'a> {
             for repo in repos {
                 let repository = Repository {
                     full_name: format!("{}/{}", repo.0, repo.1),
+                    default_branch: None,
                 };
 
                 for QueryMap { name, kind, query } in queries {
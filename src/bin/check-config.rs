@@ -0,0 +1,45 @@
+use triagebot::config;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut org_path = None;
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schema" => {
+                println!("{}", config::json_schema());
+                return;
+            }
+            "--org" => org_path = Some(args.next().unwrap_or_else(|| usage())),
+            _ if arg.starts_with('-') || path.is_some() => usage(),
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.unwrap_or_else(|| "triagebot.toml".to_owned());
+    let org = org_path.as_deref().map(read);
+    let problems = config::validate(org.as_deref(), &read(&path));
+    if problems.is_empty() {
+        println!("{}: OK", path);
+    } else {
+        for problem in &problems {
+            eprintln!("{}: {}", path, problem);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn read(path: &str) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", path, e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: check-config [--schema | [--org <organization's triagebot.toml>] <path>]");
+    std::process::exit(2);
+}
@@ -0,0 +1,34 @@
+use reqwest::Client;
+use triagebot::{db, github, handlers, handlers::Context, webhook_queue};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (dry_run, delivery_id) = match &args[..] {
+        [id] => (false, id),
+        [flag, id] if flag == "--dry-run" => (true, id),
+        _ => {
+            eprintln!("Usage: replay [--dry-run] <delivery-id>");
+            std::process::exit(1);
+        }
+    };
+
+    let gh = github::GithubClient::new_with_default_token(Client::new());
+    let ctx = Context {
+        username: handlers::bot_username(),
+        db: db::ClientPool::new(),
+        octocrab: gh.octocrab().expect("Failed to build octograb."),
+        github: gh,
+    };
+
+    match webhook_queue::replay(&ctx, delivery_id, dry_run).await {
+        Ok(report) => println!("{}", report),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            std::process::exit(1);
+        }
+    }
+}
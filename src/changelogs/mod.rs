@@ -3,7 +3,7 @@ mod rustc;
 use comrak::{nodes::AstNode, Arena, ComrakOptions, ComrakRenderOptions};
 use std::collections::HashMap;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub(crate) enum ChangelogFormat {
     Rustc,
@@ -1,22 +1,33 @@
 use crate::changelogs::ChangelogFormat;
 use crate::github::GithubClient;
+use parser::command::Command;
+use schemars::schema::{RootSchema, Schema, SchemaObject};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use tracing as log;
 
-static CONFIG_FILE_NAME: &str = "triagebot.toml";
+pub(crate) static CONFIG_FILE_NAME: &str = "triagebot.toml";
+/// The repository of an organization holding its default configuration, which applies
+/// to all of its repositories.
+static ORG_CONFIG_REPO: &str = ".github";
 const REFRESH_EVERY: Duration = Duration::from_secs(2 * 60); // Every two minutes
 
 lazy_static::lazy_static! {
-    static ref CONFIG_CACHE:
-        RwLock<HashMap<String, (Result<Arc<Config>, ConfigurationError>, Instant)>> =
-        RwLock::new(HashMap::new());
+    static ref CONFIG_CACHE: RwLock<HashMap<String, CachedConfig>> = RwLock::new(HashMap::new());
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+struct CachedConfig {
+    config: Result<Arc<Config>, ConfigurationError>,
+    fetched_at: Instant,
+    /// The last failure to fetch the configuration, which may be older than `config`.
+    last_error: Option<(ConfigurationError, Instant)>,
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct Config {
     pub(crate) relabel: Option<RelabelConfig>,
     pub(crate) assign: Option<AssignConfig>,
@@ -32,15 +43,23 @@ pub(crate) struct Config {
     pub(crate) review_submitted: Option<ReviewSubmittedConfig>,
     pub(crate) shortcut: Option<ShortcutConfig>,
     pub(crate) note: Option<NoteConfig>,
+    pub(crate) ci_failure: Option<CiFailureConfig>,
+    pub(crate) review_requests: Option<ReviewRequestsConfig>,
+    pub(crate) notifications: Option<NotificationsConfig>,
+    pub(crate) aliases: Option<AliasesConfig>,
+    pub(crate) ignore: Option<IgnoreConfig>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct NominateConfig {
     // team name -> label
     pub(crate) teams: HashMap<String, String>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+// The schema of a flattened map would lose the type of its values.
+#[schemars(transparent)]
 pub(crate) struct PingConfig {
     // team name -> message
     // message will have the cc string appended
@@ -62,9 +81,14 @@ impl PingConfig {
 
         None
     }
+
+    pub(crate) fn team_names(&self) -> impl Iterator<Item = &str> {
+        self.teams.keys().map(|team| team.as_str())
+    }
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct PingTeamConfig {
     pub(crate) message: String,
     #[serde(default)]
@@ -72,37 +96,46 @@ pub(crate) struct PingTeamConfig {
     pub(crate) label: Option<String>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct AssignConfig {
     #[serde(default)]
+    #[schemars(skip)]
     _empty: (),
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct NoteConfig {
     #[serde(default)]
+    #[schemars(skip)]
     _empty: (),
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct RelabelConfig {
     #[serde(default)]
     pub(crate) allow_unauthenticated: Vec<String>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct ShortcutConfig {
     #[serde(default)]
+    #[schemars(skip)]
     _empty: (),
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct PrioritizeConfig {
     pub(crate) label: String,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(transparent)]
 pub(crate) struct AutolabelConfig {
     #[serde(flatten)]
     pub(crate) labels: HashMap<String, AutolabelLabelConfig>,
@@ -120,7 +153,8 @@ impl AutolabelConfig {
     }
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct AutolabelLabelConfig {
     #[serde(default)]
     pub(crate) trigger_labels: Vec<String>,
@@ -130,13 +164,15 @@ pub(crate) struct AutolabelLabelConfig {
     pub(crate) trigger_files: Vec<String>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(transparent)]
 pub(crate) struct NotifyZulipConfig {
     #[serde(flatten)]
     pub(crate) labels: HashMap<String, NotifyZulipLabelConfig>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct NotifyZulipLabelConfig {
     pub(crate) zulip_stream: u64,
     pub(crate) topic: String,
@@ -148,7 +184,8 @@ pub(crate) struct NotifyZulipLabelConfig {
     pub(crate) required_labels: Vec<String>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct MajorChangeConfig {
     pub(crate) zulip_ping: String,
     pub(crate) second_label: String,
@@ -157,35 +194,138 @@ pub(crate) struct MajorChangeConfig {
     pub(crate) open_extra_text: Option<String>,
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct GlacierConfig {}
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct CloseConfig {}
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct ReviewSubmittedConfig {
     pub(crate) review_labels: Vec<String>,
     pub(crate) reviewed_label: String,
 }
 
-pub(crate) async fn get(gh: &GithubClient, repo: &str) -> Result<Arc<Config>, ConfigurationError> {
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct CiFailureConfig {
+    /// Label to add to pull requests whose CI failed, e.g. `S-waiting-on-author`.
+    pub(crate) label: Option<String>,
+    /// Labels to remove from pull requests whose CI failed.
+    #[serde(default)]
+    pub(crate) remove_labels: Vec<String>,
+    /// Whether to ping the author of the pull request with the failed jobs.
+    #[serde(default)]
+    pub(crate) ping_author: bool,
+    /// Names of the GitHub Actions workflows, or of the apps running other CI, to
+    /// react to. All of them if empty.
+    #[serde(default)]
+    pub(crate) workflows: Vec<String>,
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct ReviewRequestsConfig {
+    /// Label to apply when a draft pull request is marked as ready for review, e.g.
+    /// `S-waiting-on-review`.
+    pub(crate) ready_label: Option<String>,
+    /// Whether to record a notification for the users whose review is requested.
+    #[serde(default)]
+    pub(crate) notify_reviewers: bool,
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct NotificationsConfig {
+    /// Users notified of every new issue and pull request.
+    #[serde(default)]
+    pub(crate) subscribers: Vec<String>,
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(transparent)]
+pub(crate) struct AliasesConfig {
+    // alias -> command it expands to, without the bot mention
+    #[serde(flatten)]
+    pub(crate) aliases: HashMap<String, String>,
+}
+
+/// Which parts of comments are ignored when looking for commands and mentions, besides
+/// code, block quotes and HTML comments.
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct IgnoreConfig {
+    /// The messages quoted in replies sent by email.
+    #[serde(default)]
+    pub(crate) email_replies: bool,
+}
+
+/// Returns the configuration of `repo`, read from its default branch. If the caller
+/// knows the default branch (e.g. from a webhook payload), it is passed as
+/// `default_branch`, otherwise it is looked up.
+pub(crate) async fn get(
+    gh: &GithubClient,
+    repo: &str,
+    default_branch: Option<&str>,
+) -> Result<Arc<Config>, ConfigurationError> {
     if let Some(config) = get_cached_config(repo) {
         log::trace!("returning config for {} from cache", repo);
         config
     } else {
         log::trace!("fetching fresh config for {}", repo);
-        let res = get_fresh_config(gh, repo).await;
-        CONFIG_CACHE
-            .write()
-            .unwrap()
-            .insert(repo.to_string(), (res.clone(), Instant::now()));
+        let res = get_fresh_config(gh, repo, default_branch).await;
+        let mut cache = CONFIG_CACHE.write().unwrap();
+        let last_error = match &res {
+            Ok(_) => cache.get(repo).and_then(|cached| cached.last_error.clone()),
+            Err(e) => Some((e.clone(), Instant::now())),
+        };
+        cache.insert(
+            repo.to_string(),
+            CachedConfig {
+                config: res.clone(),
+                fetched_at: Instant::now(),
+                last_error,
+            },
+        );
+        // Failures are kept for as long as a configuration would be, so that the cache
+        // doesn't keep growing with repositories the bot can't read.
+        cache.retain(|_, cached| {
+            cached.config.is_ok() || cached.fetched_at.elapsed() < REFRESH_EVERY
+        });
         res
     }
 }
 
-#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+/// The state of the cached configuration of a repository.
+pub(crate) struct CacheStatus {
+    pub(crate) config: Result<Arc<Config>, ConfigurationError>,
+    /// Time since the configuration was fetched.
+    pub(crate) age: Duration,
+    /// The last failure to fetch the configuration, and the time since it happened.
+    pub(crate) last_error: Option<(ConfigurationError, Duration)>,
+}
+
+pub(crate) fn cache_status(repo: &str) -> Option<CacheStatus> {
+    let cache = CONFIG_CACHE.read().unwrap();
+    cache.get(repo).map(|cached| CacheStatus {
+        config: cached.config.clone(),
+        age: cached.fetched_at.elapsed(),
+        last_error: cached
+            .last_error
+            .as_ref()
+            .map(|(e, at)| (e.clone(), at.elapsed())),
+    })
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "kebab-case")]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct GitHubReleasesConfig {
     pub(crate) format: ChangelogFormat,
     pub(crate) project_name: String,
@@ -195,37 +335,331 @@ pub(crate) struct GitHubReleasesConfig {
 
 fn get_cached_config(repo: &str) -> Option<Result<Arc<Config>, ConfigurationError>> {
     let cache = CONFIG_CACHE.read().unwrap();
-    cache.get(repo).and_then(|(config, fetch_time)| {
-        if fetch_time.elapsed() < REFRESH_EVERY {
-            Some(config.clone())
+    cache.get(repo).and_then(|cached| {
+        if cached.fetched_at.elapsed() < REFRESH_EVERY {
+            Some(cached.config.clone())
         } else {
             None
         }
     })
 }
 
+/// Drops the cached configuration of `repo`, so that it is fetched again on next use.
+/// For the repository holding the configuration of an organization, this drops the
+/// configuration of all repositories of the organization.
+pub(crate) fn invalidate(repo: &str) {
+    log::debug!("invalidating cached config for {}", repo);
+    let mut cache = CONFIG_CACHE.write().unwrap();
+    match repo.split_once('/') {
+        Some((org, name)) if name == ORG_CONFIG_REPO => {
+            let prefix = format!("{}/", org);
+            cache.retain(|repo, _| !repo.starts_with(&prefix));
+        }
+        _ => {
+            cache.remove(repo);
+        }
+    }
+}
+
+/// Fetches the default configuration of the organization owning `repo`, if it has one.
+pub(crate) async fn get_org_config(
+    gh: &GithubClient,
+    repo: &str,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    match repo.split_once('/') {
+        Some((org, name)) if name != ORG_CONFIG_REPO => {
+            // `HEAD` is the default branch.
+            gh.raw_file(
+                &format!("{}/{}", org, ORG_CONFIG_REPO),
+                "HEAD",
+                CONFIG_FILE_NAME,
+            )
+            .await
+        }
+        _ => Ok(None),
+    }
+}
+
 async fn get_fresh_config(
     gh: &GithubClient,
     repo: &str,
+    default_branch: Option<&str>,
 ) -> Result<Arc<Config>, ConfigurationError> {
+    let branch = match default_branch {
+        Some(branch) => branch.to_owned(),
+        None => gh
+            .default_branch(repo)
+            .await
+            .map_err(|e| ConfigurationError::Http(Arc::new(e)))?,
+    };
     let contents = gh
-        .raw_file(repo, "master", CONFIG_FILE_NAME)
+        .raw_file(repo, &branch, CONFIG_FILE_NAME)
+        .await
+        .map_err(|e| ConfigurationError::Http(Arc::new(e)))?;
+    let org_contents = get_org_config(gh, repo)
         .await
-        .map_err(|e| ConfigurationError::Http(Arc::new(e)))?
-        .ok_or(ConfigurationError::Missing)?;
-    let config = Arc::new(toml::from_slice::<Config>(&contents).map_err(ConfigurationError::Toml)?);
+        .map_err(|e| ConfigurationError::Http(Arc::new(e)))?;
+    let config = Arc::new(parse(org_contents.as_deref(), contents.as_deref())?);
     log::debug!("fresh configuration for {}: {:?}", repo, config);
     Ok(config)
 }
 
+/// Parses the configuration of a repository, on top of the default configuration of
+/// its organization if there is one.
+///
+/// Setting a section to `false` turns it off, along with the organization's default
+/// for it.
+pub(crate) fn parse(org: Option<&[u8]>, repo: Option<&[u8]>) -> Result<Config, ConfigurationError> {
+    let mut config = match (org, repo) {
+        (None, None) => return Err(ConfigurationError::Missing),
+        (Some(org), _) => toml::from_slice(org).map_err(ConfigurationError::OrgToml)?,
+        (None, Some(_)) => toml::value::Table::new(),
+    };
+    if let Some(repo) = repo {
+        let mut overrides: toml::value::Table =
+            toml::from_slice(repo).map_err(ConfigurationError::Toml)?;
+        let disabled = overrides
+            .iter()
+            .filter(|(_, section)| section.as_bool() == Some(false))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if org.is_none() && disabled.is_empty() {
+            // Parsing the file directly gives errors with line numbers.
+            return toml::from_slice(repo).map_err(ConfigurationError::Toml);
+        }
+        for section in disabled {
+            config.remove(&section);
+            overrides.remove(&section);
+        }
+        merge(&mut config, overrides);
+    }
+    toml::Value::Table(config)
+        .try_into()
+        .map_err(ConfigurationError::Toml)
+}
+
+/// Merges `overrides` into `base`: tables are merged key by key, while other values in
+/// `overrides` replace those in `base`.
+fn merge(base: &mut toml::value::Table, overrides: toml::value::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(value)) => {
+                merge(existing, value)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Checks the contents of a `triagebot.toml`, returning a description of each problem
+/// found.
+///
+/// Besides syntax errors, unknown keys and values of the wrong type, this reports label
+/// patterns which are not valid globs. `org` is the default configuration of the
+/// organization, if any.
+pub fn validate(org: Option<&str>, contents: &str) -> Vec<String> {
+    match check(org.map(str::as_bytes), contents.as_bytes()) {
+        Ok((_, problems)) => problems,
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+/// Parses the configuration like `parse`, also returning the problems which don't
+/// prevent using it: those found by `Config::problems`, and the keys in `repo` which
+/// the configuration does not have.
+///
+/// Unknown keys are ignored when the configuration is used, so that a typo only loses
+/// the setting it was meant for rather than disabling every handler of the repository.
+pub(crate) fn check(
+    org: Option<&[u8]>,
+    repo: &[u8],
+) -> Result<(Config, Vec<String>), ConfigurationError> {
+    let config = parse(org, Some(repo))?;
+    let mut problems = config.problems();
+    let repo = toml::from_slice(repo).map_err(ConfigurationError::Toml)?;
+    let schema = schema();
+    let root = Schema::Object(schema.schema.clone());
+    unknown_keys(&schema, &root, &repo, "", &mut problems);
+    problems.sort();
+    Ok((config, problems))
+}
+
+/// Adds a problem for each key of `value`, found at `path`, which `schema` does not
+/// allow.
+fn unknown_keys(
+    root: &RootSchema,
+    schema: &Schema,
+    value: &toml::Value,
+    path: &str,
+    problems: &mut Vec<String>,
+) {
+    let (schema, table) = match (schema, value) {
+        (Schema::Object(schema), toml::Value::Table(table)) => (schema, table),
+        _ => return,
+    };
+    if let Some(reference) = &schema.reference {
+        let name = reference.trim_start_matches("#/definitions/");
+        if let Some(schema) = root.definitions.get(name) {
+            unknown_keys(root, schema, value, path, problems);
+        }
+        return;
+    }
+    // Optional sections are described as one of their type and null.
+    if let Some(subschemas) = &schema.subschemas {
+        let all = subschemas.all_of.iter().chain(&subschemas.any_of);
+        for schema in all.chain(&subschemas.one_of).flatten() {
+            unknown_keys(root, schema, value, path, problems);
+        }
+    }
+    let object = match &schema.object {
+        Some(object) => object,
+        None => return,
+    };
+    for (key, value) in table {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match (
+            object.properties.get(key),
+            object.additional_properties.as_deref(),
+        ) {
+            (Some(schema), _) => unknown_keys(root, schema, value, &path, problems),
+            (None, Some(Schema::Bool(false))) => problems.push(format!("unknown key `{}`", path)),
+            (None, Some(schema)) => unknown_keys(root, schema, value, &path, problems),
+            (None, None) => {}
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn ignore_options(&self) -> parser::IgnoreOptions {
+        parser::IgnoreOptions {
+            email_replies: self.ignore.as_ref().map_or(false, |i| i.email_replies),
+        }
+    }
+
+    /// Describes the problems in this configuration which deserializing it does not
+    /// catch: label patterns which are not valid globs, and invalid command aliases.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut check_pattern = |key: &str, pattern: &str| {
+            if let Err(e) = glob::Pattern::new(pattern) {
+                problems.push(format!("invalid pattern `{}` in `{}`: {}", pattern, key, e));
+            }
+        };
+        if let Some(relabel) = &self.relabel {
+            for pattern in &relabel.allow_unauthenticated {
+                let pattern = pattern.strip_prefix('!').unwrap_or(pattern);
+                check_pattern("relabel.allow-unauthenticated", pattern);
+            }
+        }
+        if let Some(autolabel) = &self.autolabel {
+            for (label, cfg) in &autolabel.labels {
+                for pattern in &cfg.exclude_labels {
+                    check_pattern(&format!("autolabel.{}.exclude_labels", label), pattern);
+                }
+            }
+        }
+        if let Some(notify_zulip) = &self.notify_zulip {
+            for (label, cfg) in &notify_zulip.labels {
+                for pattern in &cfg.required_labels {
+                    check_pattern(&format!("notify-zulip.{}.required_labels", label), pattern);
+                }
+            }
+        }
+        if let Some(aliases) = &self.aliases {
+            let mut names = aliases.aliases.keys().collect::<Vec<_>>();
+            names.sort();
+            for (i, alias) in names.iter().enumerate() {
+                if let Some(other) = names[..i]
+                    .iter()
+                    .find(|other| other.eq_ignore_ascii_case(alias))
+                {
+                    problems.push(format!(
+                        "aliases `{}` and `{}` differ only in case",
+                        other, alias
+                    ));
+                }
+            }
+            for (alias, expansion) in &aliases.aliases {
+                if parser::command::is_command_word(alias) {
+                    problems.push(format!("alias `{}` is a built-in command", alias));
+                    continue;
+                }
+                let command = format!("@triagebot {}", expansion);
+                let expanded = parser::command::expand_aliases(
+                    &format!("@triagebot {}", alias),
+                    &["triagebot"],
+                    &aliases.aliases,
+                )
+                .into_owned();
+                if expanded != command {
+                    problems.push(format!("alias `{}` is not a single word", alias));
+                }
+                // Arguments can be left for the user to give, so only the start of the
+                // command is checked.
+                let parsed = parser::command::Input::new(&command, vec!["triagebot"]).next();
+                if matches!(
+                    parsed,
+                    None | Some(Command::Unknown(_)) | Some(Command::Ambiguous(_))
+                ) {
+                    problems.push(format!(
+                        "alias `{}` does not expand to a command: `{}`",
+                        alias, expansion
+                    ));
+                }
+            }
+        }
+        problems.sort();
+        problems
+    }
+}
+
+/// The JSON schema of `triagebot.toml`, for use by editors.
+pub fn json_schema() -> String {
+    serde_json::to_string_pretty(&schema()).unwrap()
+}
+
+fn schema() -> RootSchema {
+    let mut schema = schemars::schema_for!(Config);
+    // Any section can be set to `false` to turn it off.
+    for section in schema.schema.object().properties.values_mut() {
+        if let Schema::Object(section) = section {
+            let disabled = SchemaObject {
+                const_value: Some(false.into()),
+                ..Default::default()
+            };
+            section
+                .subschemas()
+                .any_of
+                .get_or_insert_with(Vec::new)
+                .push(disabled.into());
+        }
+    }
+    schema
+}
+
 #[derive(Clone, Debug)]
 pub enum ConfigurationError {
     Missing,
     Toml(toml::de::Error),
+    /// The default configuration of the organization is malformed.
+    OrgToml(toml::de::Error),
     Http(Arc<anyhow::Error>),
 }
 
-impl std::error::Error for ConfigurationError {}
+impl std::error::Error for ConfigurationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigurationError::Http(e) => Some(&***e),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for ConfigurationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -233,11 +667,16 @@ impl fmt::Display for ConfigurationError {
             ConfigurationError::Missing => write!(
                 f,
                 "This repository is not enabled to use triagebot.\n\
-                 Add a `triagebot.toml` in the root of the master branch to enable it."
+                 Add a `triagebot.toml` in the root of the default branch to enable it."
             ),
             ConfigurationError::Toml(e) => {
-                write!(f, "Malformed `triagebot.toml` in master branch.\n{}", e)
+                write!(f, "Malformed `triagebot.toml` in default branch.\n{}", e)
             }
+            ConfigurationError::OrgToml(e) => write!(
+                f,
+                "Malformed `triagebot.toml` in the `{}` repository of the organization.\n{}",
+                ORG_CONFIG_REPO, e
+            ),
             ConfigurationError::Http(_) => {
                 write!(f, "Failed to query configuration for this repository.")
             }
@@ -324,7 +763,210 @@ mod tests {
                 notify_zulip: None,
                 github_releases: None,
                 review_submitted: None,
+                ci_failure: None,
+                review_requests: None,
+                notifications: None,
+                aliases: None,
+                ignore: None,
             }
         );
     }
+    #[test]
+    fn unknown_keys() {
+        let config = r#"
+            [relabel]
+            allow-unauthenticated = ["C-*"]
+            allow-unauthenticated-typo = ["A-*"]
+        "#;
+        let problems = validate(None, config);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("allow-unauthenticated-typo"));
+
+        assert_eq!(validate(None, "[no-such-handler]").len(), 1);
+
+        let config = r#"
+            typo = true
+            [relabel]
+            allow-unauthenticated = ["C-*"]
+            typo = true
+            [autolabel."S-waiting-on-review"]
+            trigger_labels = ["A-*"]
+            typo = true
+        "#;
+        assert_eq!(
+            validate(Some("[assign]"), config),
+            [
+                "unknown key `autolabel.S-waiting-on-review.typo`",
+                "unknown key `relabel.typo`",
+                "unknown key `typo`",
+            ]
+        );
+        // The bot keeps working for the rest of the configuration.
+        let config = parse(None, Some(config.as_bytes())).unwrap();
+        assert_eq!(
+            config.relabel.unwrap().allow_unauthenticated,
+            ["C-*".to_owned()]
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        let config = r#"
+            [notify-zulip."I-prioritize"]
+            zulip_stream = "t-compiler"
+            topic = "issue {number}"
+        "#;
+        assert_eq!(validate(None, config).len(), 1);
+
+        let config = r#"
+            [relabel]
+            allow-unauthenticated = ["!C-[", "T-*"]
+
+            [autolabel."S-waiting-on-review"]
+            exclude_labels = ["S-***"]
+
+            [notify-zulip."I-prioritize"]
+            zulip_stream = 245100
+            topic = "issue {number}"
+            required_labels = ["P-*"]
+        "#;
+        assert_eq!(
+            validate(None, config),
+            vec![
+                "invalid pattern `C-[` in `relabel.allow-unauthenticated`: \
+                 Pattern syntax error near position 2: invalid range pattern",
+                "invalid pattern `S-***` in `autolabel.S-waiting-on-review.exclude_labels`: \
+                 Pattern syntax error near position 4: wildcards are either regular `*` or recursive `**`",
+            ]
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        let config = r#"
+            [aliases]
+            ptal = "ready"
+            "needs-design" = "label +S-needs-design"
+        "#;
+        assert!(validate(None, config).is_empty());
+
+        let config = r#"
+            [aliases]
+            "take a look" = "ready"
+            fixed = "resolve"
+            nom = "nominate"
+        "#;
+        assert_eq!(
+            validate(None, config),
+            vec![
+                "alias `fixed` does not expand to a command: `resolve`",
+                "alias `take a look` is not a single word",
+            ]
+        );
+
+        let config = r#"
+            [aliases]
+            close = "label +S-blocked"
+            Nominate = "label +I-nominated"
+            PTAL = "ready"
+            ptal = "ready"
+        "#;
+        assert_eq!(
+            validate(None, config),
+            vec![
+                "alias `Nominate` is a built-in command",
+                "alias `close` is a built-in command",
+                "aliases `PTAL` and `ptal` differ only in case",
+            ]
+        );
+    }
+
+    #[test]
+    fn schema() {
+        let schema: serde_json::Value = serde_json::from_str(&json_schema()).unwrap();
+        assert!(schema["properties"]["notify-zulip"].is_object());
+        assert_eq!(schema["additionalProperties"], false);
+    }
+    #[test]
+    fn org_defaults() {
+        let org = r#"
+            [ping.wg-meta]
+            message = "Org-wide ping"
+            label = "T-meta"
+
+            [notifications]
+            subscribers = ["dtolnay"]
+
+            [prioritize]
+            label = "I-prioritize"
+        "#;
+        let repo = r#"
+            [ping.wg-meta]
+            message = "Repo ping"
+
+            [ping.compiler]
+            message = "Compiler ping"
+
+            [prioritize]
+            label = "P-triage"
+        "#;
+        let config = parse(Some(org.as_bytes()), Some(repo.as_bytes())).unwrap();
+        let ping = config.ping.unwrap();
+        let (_, wg_meta) = ping.get_by_name("wg-meta").unwrap();
+        assert_eq!(wg_meta.message, "Repo ping");
+        assert_eq!(wg_meta.label.as_deref(), Some("T-meta"));
+        assert!(ping.get_by_name("compiler").is_some());
+        assert_eq!(config.prioritize.unwrap().label, "P-triage");
+        assert_eq!(config.notifications.unwrap().subscribers, vec!["dtolnay"]);
+
+        // The organization's configuration also applies to repositories without one.
+        let config = parse(Some(org.as_bytes()), None).unwrap();
+        assert_eq!(config.prioritize.unwrap().label, "I-prioritize");
+
+        assert!(matches!(
+            parse(None, None),
+            Err(ConfigurationError::Missing)
+        ));
+        assert!(matches!(
+            parse(Some(b"[ping"), Some(repo.as_bytes())),
+            Err(ConfigurationError::OrgToml(_))
+        ));
+    }
+
+    #[test]
+    fn disabled_sections() {
+        let org = r#"
+            [notifications]
+            subscribers = ["dtolnay"]
+
+            [prioritize]
+            label = "I-prioritize"
+        "#;
+        let repo = r#"
+            notifications = false
+            relabel = false
+
+            [prioritize]
+            label = "P-triage"
+        "#;
+        let config = parse(Some(org.as_bytes()), Some(repo.as_bytes())).unwrap();
+        assert!(config.notifications.is_none());
+        assert!(config.relabel.is_none());
+        assert_eq!(config.prioritize.unwrap().label, "P-triage");
+        assert!(validate(Some(org), repo).is_empty());
+
+        // Without an organization, there is nothing to turn off.
+        let config = parse(None, Some(repo.as_bytes())).unwrap();
+        assert!(config.relabel.is_none());
+        assert!(config.prioritize.is_some());
+
+        // Only whole sections can be turned off.
+        assert!(parse(None, Some(b"[relabel]\nallow-unauthenticated = false")).is_err());
+
+        let schema: serde_json::Value = serde_json::from_str(&json_schema()).unwrap();
+        assert!(schema["properties"]["relabel"]["anyOf"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!({ "const": false })));
+    }
 }
@@ -0,0 +1,113 @@
+//! The `/config/:owner/:repo` page, showing the configuration the bot uses for a
+//! repository and the handlers it enables.
+//!
+//! The page is public, so it only shows what is already cached: the configuration of
+//! a repository is loaded when the bot receives an event from it, and rendering the
+//! page never makes requests to GitHub.
+
+use crate::config::{self, Config};
+use crate::handlers::{self, Context, HandlerInfo};
+use std::time::Duration;
+
+pub fn render(ctx: &Context, repo: &str) -> String {
+    let status = config::cache_status(repo);
+
+    let mut out = String::new();
+    out.push_str("<html>");
+    out.push_str("<head>");
+    out.push_str("<meta charset=\"utf-8\">");
+    out.push_str("<title>Triagebot Configuration</title>");
+    out.push_str("</head>");
+    out.push_str("<body>");
+
+    out.push_str(&format!("<h3>Configuration of {}</h3>", escape(repo)));
+
+    let status = match status {
+        Some(status) => status,
+        None => {
+            out.push_str(
+                "<p><em>The configuration is not loaded; it is loaded when the bot \
+                 receives an event from the repository.</em></p>",
+            );
+            out.push_str("</body>");
+            out.push_str("</html>");
+            return out;
+        }
+    };
+    out.push_str(&format!("<p>Fetched {} ago.</p>", ago(status.age)));
+    if let Some((error, age)) = &status.last_error {
+        out.push_str(&format!(
+            "<p>Last error, {} ago:</p><pre>{}</pre>",
+            ago(*age),
+            escape(&error.to_string())
+        ));
+    }
+
+    match &status.config {
+        Ok(config) => {
+            out.push_str("<h4>Commands</h4>");
+            render_handlers(&mut out, &handlers::command_handlers(config), |usage| {
+                format!("<code>@{} {}</code>", escape(&ctx.username), escape(usage))
+            });
+            out.push_str("<h4>Issue and pull request handlers</h4>");
+            render_handlers(&mut out, &handlers::issue_handlers(config), escape);
+            render_config(&mut out, config);
+        }
+        Err(e) => {
+            out.push_str(&format!(
+                "<p>No configuration is in use:</p><pre>{}</pre>",
+                escape(&e.to_string())
+            ));
+        }
+    }
+
+    out.push_str("</body>");
+    out.push_str("</html>");
+    out
+}
+
+fn render_handlers(out: &mut String, handlers: &[HandlerInfo], usage: impl Fn(&str) -> String) {
+    out.push_str("<ul>");
+    for handler in handlers {
+        out.push_str("<li>");
+        let state = if handler.enabled {
+            "enabled"
+        } else {
+            "not enabled"
+        };
+        out.push_str(&format!("<b>{}</b> ({})", handler.name, state));
+        if !handler.usage.is_empty() {
+            let usage = handler
+                .usage
+                .iter()
+                .map(|u| usage(u))
+                .collect::<Vec<_>>()
+                .join(", ");
+            out.push_str(&format!(": {}", usage));
+        }
+        out.push_str("</li>");
+    }
+    out.push_str("</ul>");
+}
+
+fn render_config(out: &mut String, config: &Config) {
+    out.push_str("<h4>Parsed configuration</h4>");
+    out.push_str(&format!("<pre>{}</pre>", escape(&format!("{:#?}", config))));
+}
+
+fn ago(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{} seconds", secs)
+    } else {
+        format!("{} minutes", secs / 60)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -2,11 +2,13 @@ use anyhow::Context as _;
 use native_tls::{Certificate, TlsConnector};
 use postgres_native_tls::MakeTlsConnector;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio_postgres::Client as DbClient;
 
 pub mod notifications;
 pub mod rustc_commits;
+pub mod webhook_events;
 
 const CERT_URL: &str = "https://s3.amazonaws.com/rds-downloads/rds-ca-2019-root.pem";
 
@@ -21,6 +23,7 @@ lazy_static::lazy_static! {
     };
 }
 
+#[derive(Clone)]
 pub struct ClientPool {
     connections: Arc<Mutex<Vec<tokio_postgres::Client>>>,
     permits: Arc<Semaphore>,
@@ -63,27 +66,35 @@ impl ClientPool {
     }
 
     pub async fn get(&self) -> PooledClient {
+        self.try_get().await.unwrap()
+    }
+
+    /// Like `get`, but returns an error instead of panicking if a new connection
+    /// cannot be established.
+    pub async fn try_get(&self) -> anyhow::Result<PooledClient> {
+        let started = Instant::now();
         let permit = self.permits.clone().acquire_owned().await.unwrap();
+        crate::metrics::record_db_pool_wait(started);
         {
             let mut slots = self.connections.lock().unwrap_or_else(|e| e.into_inner());
             // Pop connections until we hit a non-closed connection (or there are no
             // "possibly open" connections left).
             while let Some(c) = slots.pop() {
                 if !c.is_closed() {
-                    return PooledClient {
+                    return Ok(PooledClient {
                         client: Some(c),
                         permit,
                         pool: self.connections.clone(),
-                    };
+                    });
                 }
             }
         }
 
-        PooledClient {
-            client: Some(make_client().await.unwrap()),
+        Ok(PooledClient {
+            client: Some(make_client().await?),
             permit,
             pool: self.connections.clone(),
-        }
+        })
     }
 }
 
@@ -130,6 +141,36 @@ async fn make_client() -> anyhow::Result<tokio_postgres::Client> {
     }
 }
 
+/// Connects to the database at `TEST_DATABASE_URL`, with the tables created as
+/// temporary tables which are dropped when the connection closes.
+///
+/// Returns `None` if the variable is not set, in which case tests using the database
+/// are skipped.
+#[cfg(test)]
+pub(crate) async fn test_client() -> Option<DbClient> {
+    let db_url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("TEST_DATABASE_URL is not set, skipping test");
+            return None;
+        }
+    };
+    let (client, connection) = tokio_postgres::connect(&db_url, tokio_postgres::NoTls)
+        .await
+        .expect("failed to connect to test database");
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("database connection error: {}", e);
+        }
+    });
+    client
+        .execute("SET search_path TO pg_temp", &[])
+        .await
+        .unwrap();
+    run_migrations(&client).await.unwrap();
+    Some(client)
+}
+
 pub async fn run_migrations(client: &DbClient) -> anyhow::Result<()> {
     client
         .execute(
@@ -206,4 +247,21 @@ CREATE TABLE rustc_commits (
 );
 ",
     "ALTER TABLE rustc_commits ADD COLUMN pr INTEGER;",
+    "
+CREATE TABLE webhook_events (
+    event_id BIGSERIAL PRIMARY KEY,
+    event_name TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    last_error TEXT,
+    received_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+",
+    "CREATE INDEX webhook_events_status_next_attempt ON webhook_events (status, next_attempt);",
+    "ALTER TABLE webhook_events ADD COLUMN delivery_id TEXT;",
+    "CREATE UNIQUE INDEX webhook_events_delivery_id ON webhook_events (delivery_id);",
+    "ALTER TABLE webhook_events ADD COLUMN repo TEXT;",
+    "ALTER TABLE webhook_events ADD COLUMN completed_handlers TEXT[] NOT NULL DEFAULT '{}';",
 ];
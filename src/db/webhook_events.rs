@@ -0,0 +1,314 @@
+//! Storage for the durable webhook queue (see `crate::webhook_queue`).
+//!
+//! Every event goes through the following states:
+//!
+//! * `pending`: waiting to be picked up by a worker, no earlier than `next_attempt`.
+//! * `processing`: claimed by a worker until `next_attempt`; if the worker dies, the
+//!   event is picked up again once that lease expires.
+//! * `done`: all handlers ran successfully.
+//! * `dead`: handling failed too many times, or in a way retrying would not fix; kept
+//!   around for inspection.
+//!
+//! Handled events are kept for a retention window, which allows replaying them.
+
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset};
+use std::time::Duration;
+use tokio_postgres::Client as DbClient;
+
+/// A webhook event claimed by a worker.
+#[derive(Debug)]
+pub struct QueuedEvent {
+    pub event_id: i64,
+    pub event_name: String,
+    pub payload: String,
+    /// Number of times this event has been claimed, including the current one.
+    pub attempts: i32,
+    pub received_at: DateTime<FixedOffset>,
+    /// Handlers which are done with the event after earlier attempts, see `crate::webhook`.
+    pub completed_handlers: Vec<String>,
+}
+
+/// A webhook delivery as it was received.
+#[derive(Debug)]
+pub struct StoredEvent {
+    pub event_id: i64,
+    pub event_name: String,
+    pub delivery_id: Option<String>,
+    pub repo: Option<String>,
+    pub payload: String,
+    pub status: String,
+    pub received_at: DateTime<FixedOffset>,
+}
+
+/// Adds an event to the queue, returning its id.
+///
+/// Deliveries are deduplicated by their `X-GitHub-Delivery` id: if the delivery was
+/// seen before, `None` is returned and the event is not queued again, unless it ended
+/// up in the dead-letter state. A dead delivery is queued again from scratch: its
+/// payload is replaced, so all handlers run again.
+pub async fn enqueue(
+    db: &DbClient,
+    event_name: &str,
+    payload: &str,
+    delivery_id: Option<&str>,
+    repo: Option<&str>,
+) -> anyhow::Result<Option<i64>> {
+    let row = db
+        .query_opt(
+            "INSERT INTO webhook_events (event_name, payload, delivery_id, repo)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (delivery_id) DO UPDATE
+            SET event_name = EXCLUDED.event_name,
+                payload = EXCLUDED.payload,
+                repo = EXCLUDED.repo,
+                status = 'pending',
+                attempts = 0,
+                next_attempt = now(),
+                last_error = NULL,
+                completed_handlers = '{}'
+            WHERE webhook_events.status = 'dead'
+            RETURNING event_id",
+            &[&event_name, &payload, &delivery_id, &repo],
+        )
+        .await
+        .context("inserting webhook event")?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Claims the oldest event which is due for processing, leasing it for `lease`.
+pub async fn claim_next(db: &DbClient, lease: Duration) -> anyhow::Result<Option<QueuedEvent>> {
+    let row = db
+        .query_opt(
+            "UPDATE webhook_events
+            SET status = 'processing',
+                attempts = attempts + 1,
+                next_attempt = now() + make_interval(secs => $1)
+            WHERE event_id = (
+                SELECT event_id
+                FROM webhook_events
+                WHERE status IN ('pending', 'processing') AND next_attempt <= now()
+                ORDER BY event_id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING event_id, event_name, payload, attempts, received_at, completed_handlers",
+            &[&lease.as_secs_f64()],
+        )
+        .await
+        .context("claiming webhook event")?;
+    Ok(row.map(|row| QueuedEvent {
+        event_id: row.get(0),
+        event_name: row.get(1),
+        payload: row.get(2),
+        attempts: row.get(3),
+        received_at: row.get(4),
+        completed_handlers: row.get(5),
+    }))
+}
+
+pub async fn mark_done(db: &DbClient, event_id: i64) -> anyhow::Result<()> {
+    db.execute(
+        "UPDATE webhook_events SET status = 'done', last_error = NULL WHERE event_id = $1",
+        &[&event_id],
+    )
+    .await
+    .context("marking webhook event as done")?;
+    Ok(())
+}
+
+/// Gives up the lease on an event whose handling was interrupted, so that it can be
+/// claimed again right away. The interrupted attempt is not counted.
+pub async fn release(db: &DbClient, event_id: i64) -> anyhow::Result<()> {
+    db.execute(
+        "UPDATE webhook_events
+        SET status = 'pending', attempts = attempts - 1, next_attempt = now()
+        WHERE event_id = $1 AND status = 'processing'",
+        &[&event_id],
+    )
+    .await
+    .context("releasing webhook event")?;
+    Ok(())
+}
+
+/// Records a failed attempt, after which the handlers in `completed` are done with the
+/// event. The event is retried after `retry_in`, or moved to the dead-letter state if
+/// that is `None`.
+pub async fn mark_failed(
+    db: &DbClient,
+    event_id: i64,
+    error: &str,
+    completed: &[String],
+    retry_in: Option<Duration>,
+) -> anyhow::Result<()> {
+    match retry_in {
+        Some(delay) => db
+            .execute(
+                "UPDATE webhook_events
+                SET status = 'pending', last_error = $2, completed_handlers = $3,
+                    next_attempt = now() + make_interval(secs => $4)
+                WHERE event_id = $1",
+                &[&event_id, &error, &completed, &delay.as_secs_f64()],
+            )
+            .await
+            .context("scheduling webhook event retry")?,
+        None => db
+            .execute(
+                "UPDATE webhook_events
+                SET status = 'dead', last_error = $2, completed_handlers = $3
+                WHERE event_id = $1",
+                &[&event_id, &error, &completed],
+            )
+            .await
+            .context("moving webhook event to dead-letter state")?,
+    };
+    Ok(())
+}
+
+/// Looks up a stored delivery by its `X-GitHub-Delivery` id.
+pub async fn get_by_delivery(
+    db: &DbClient,
+    delivery_id: &str,
+) -> anyhow::Result<Option<StoredEvent>> {
+    let row = db
+        .query_opt(
+            "SELECT event_id, event_name, delivery_id, repo, payload, status, received_at
+            FROM webhook_events
+            WHERE delivery_id = $1",
+            &[&delivery_id],
+        )
+        .await
+        .context("getting webhook event")?;
+    Ok(row.map(|row| StoredEvent {
+        event_id: row.get(0),
+        event_name: row.get(1),
+        delivery_id: row.get(2),
+        repo: row.get(3),
+        payload: row.get(4),
+        status: row.get(5),
+        received_at: row.get(6),
+    }))
+}
+
+/// Deletes handled and dead events received more than `age` ago.
+pub async fn delete_finished(db: &DbClient, age: Duration) -> anyhow::Result<u64> {
+    db.execute(
+        "DELETE FROM webhook_events
+        WHERE status IN ('done', 'dead') AND received_at < now() - make_interval(secs => $1)",
+        &[&age.as_secs_f64()],
+    )
+    .await
+    .context("deleting finished webhook events")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn status(db: &DbClient, event_id: i64) -> (String, i32) {
+        let row = db
+            .query_one(
+                "SELECT status, attempts FROM webhook_events WHERE event_id = $1",
+                &[&event_id],
+            )
+            .await
+            .unwrap();
+        (row.get(0), row.get(1))
+    }
+
+    async fn set_status(db: &DbClient, event_id: i64, status: &str) {
+        db.execute(
+            "UPDATE webhook_events SET status = $2, attempts = 3 WHERE event_id = $1",
+            &[&event_id, &status],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn duplicate_deliveries() {
+        let db = match crate::db::test_client().await {
+            Some(db) => db,
+            None => return,
+        };
+        let id = enqueue(&db, "issues", "{}", Some("a"), Some("o/r"))
+            .await
+            .unwrap()
+            .expect("new delivery is queued");
+        assert_eq!(
+            enqueue(&db, "issues", "{}", Some("a"), Some("o/r"))
+                .await
+                .unwrap(),
+            None
+        );
+        for state in &["done", "processing"] {
+            set_status(&db, id, state).await;
+            assert_eq!(
+                enqueue(&db, "issues", "{}", Some("a"), Some("o/r"))
+                    .await
+                    .unwrap(),
+                None
+            );
+            assert_eq!(status(&db, id).await, (state.to_string(), 3));
+        }
+
+        // Deliveries without an id can't be deduplicated.
+        let first = enqueue(&db, "issues", "{}", None, None).await.unwrap();
+        let second = enqueue(&db, "issues", "{}", None, None).await.unwrap();
+        assert!(first.is_some() && second.is_some() && first != second);
+    }
+
+    #[tokio::test]
+    async fn released_events_are_claimed_again() {
+        let db = match crate::db::test_client().await {
+            Some(db) => db,
+            None => return,
+        };
+        let lease = Duration::from_secs(600);
+        let id = enqueue(&db, "issues", "{}", Some("a"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        let claimed = claim_next(&db, lease).await.unwrap().unwrap();
+        assert_eq!((claimed.event_id, claimed.attempts), (id, 1));
+        // Leased to the first worker.
+        assert!(claim_next(&db, lease).await.unwrap().is_none());
+
+        release(&db, id).await.unwrap();
+        assert_eq!(status(&db, id).await, (String::from("pending"), 0));
+        let claimed = claim_next(&db, lease).await.unwrap().unwrap();
+        assert_eq!((claimed.event_id, claimed.attempts), (id, 1));
+    }
+
+    #[tokio::test]
+    async fn dead_deliveries_are_queued_again() {
+        let db = match crate::db::test_client().await {
+            Some(db) => db,
+            None => return,
+        };
+        let id = enqueue(&db, "issues", "{}", Some("a"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        let completed = [String::from("autolabel")];
+        mark_failed(&db, id, "failed", &completed, None)
+            .await
+            .unwrap();
+        assert_eq!(status(&db, id).await, (String::from("dead"), 0));
+        assert_eq!(
+            enqueue(&db, "issues", "{\"new\": true}", Some("a"), Some("o/r"))
+                .await
+                .unwrap(),
+            Some(id)
+        );
+        assert_eq!(status(&db, id).await, (String::from("pending"), 0));
+        let stored = get_by_delivery(&db, "a").await.unwrap().unwrap();
+        assert_eq!(stored.payload, "{\"new\": true}");
+        assert_eq!(stored.repo.as_deref(), Some("o/r"));
+        let claimed = claim_next(&db, Duration::from_secs(600))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(claimed.completed_handlers.is_empty());
+    }
+}
@@ -5,17 +5,21 @@ use futures::{future::BoxFuture, FutureExt};
 use hyper::header::HeaderValue;
 use once_cell::sync::OnceCell;
 use reqwest::header::{AUTHORIZATION, USER_AGENT};
-use reqwest::{Client, Request, RequestBuilder, Response, StatusCode};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode};
+use std::cell::RefCell;
 use std::convert::TryInto;
+use std::future::Future;
+use std::sync::Arc;
 use std::{
     fmt,
     time::{Duration, SystemTime},
 };
 use tracing as log;
 
+pub mod app;
 pub mod graphql;
 
-#[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize)]
 pub struct User {
     pub login: String,
     pub id: Option<i64>,
@@ -30,12 +34,28 @@ impl GithubClient {
             .build()
             .with_context(|| format!("building reqwest {}", req_dbg))?;
 
+        // GraphQL requests are POSTs, but we only use them for queries.
+        if self.is_dry_run()
+            && req.method() != Method::GET
+            && !req.url().path().ends_with("/graphql")
+        {
+            let body = req
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| String::from_utf8_lossy(b).into_owned());
+            self.record_planned(req.method().as_str(), req.url().as_str(), body);
+            return Ok((Response::from(hyper::Response::new("{}")), req_dbg));
+        }
+
+        let method = req.method().clone();
         let mut resp = self.client.execute(req.try_clone().unwrap()).await?;
+        crate::metrics::record_github_response(&method, &resp);
         if let Some(sleep) = Self::needs_retry(&resp).await {
             resp = self.retry(req, sleep, MAX_ATTEMPTS).await?;
+            crate::metrics::record_github_response(&method, &resp);
         }
 
-        resp.error_for_status_ref()?;
+        check_status(&resp)?;
 
         Ok((resp, req_dbg))
     }
@@ -111,7 +131,7 @@ impl GithubClient {
                 .client
                 .execute(
                     self.client
-                        .get("https://api.github.com/rate_limit")
+                        .get(&format!("{}/rate_limit", self.api_url()))
                         .configure(self)
                         .build()
                         .unwrap(),
@@ -176,9 +196,46 @@ impl GithubClient {
     }
 }
 
+/// GitHub refused a request because of a rate limit, which `GithubClient` did not
+/// wait out.
+#[derive(Debug)]
+pub struct RateLimited(reqwest::Error);
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rate limited by GitHub")
+    }
+}
+
+impl std::error::Error for RateLimited {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Returns an error if `resp` is unsuccessful. GitHub answers 403 both for missing
+/// permissions and for secondary rate limits, which are told apart by their headers.
+pub(crate) fn check_status(resp: &Response) -> anyhow::Result<()> {
+    if let Err(err) = resp.error_for_status_ref() {
+        let headers = resp.headers();
+        let rate_limited = resp.status() == StatusCode::FORBIDDEN
+            && (headers
+                .get("X-RateLimit-Remaining")
+                .map_or(false, |remaining| remaining == "0")
+                || headers.contains_key(reqwest::header::RETRY_AFTER));
+        return Err(if rate_limited {
+            RateLimited(err).into()
+        } else {
+            err.into()
+        });
+    }
+    Ok(())
+}
+
 impl User {
     pub async fn current(client: &GithubClient) -> anyhow::Result<Self> {
-        client.json(client.get("https://api.github.com/user")).await
+        let url = format!("{}/user", client.api_url());
+        client.json(client.get(&url)).await
     }
 
     pub async fn is_team_member<'a>(&'a self, client: &'a GithubClient) -> anyhow::Result<bool> {
@@ -291,6 +348,7 @@ impl ZulipGitHubReference {
 
 #[derive(Debug, serde::Deserialize)]
 pub struct Comment {
+    pub id: usize,
     #[serde(deserialize_with = "opt_string")]
     pub body: String,
     pub html_url: String,
@@ -359,16 +417,22 @@ impl fmt::Display for IssueRepository {
 }
 
 impl IssueRepository {
-    fn url(&self) -> String {
+    fn url(&self, client: &GithubClient) -> String {
         format!(
-            "https://api.github.com/repos/{}/{}",
-            self.organization, self.repository
+            "{}/repos/{}/{}",
+            client.api_url(),
+            self.organization,
+            self.repository
         )
     }
 
-    async fn has_label(&self, client: &GithubClient, label: &str) -> anyhow::Result<bool> {
+    pub(crate) async fn has_label(
+        &self,
+        client: &GithubClient,
+        label: &str,
+    ) -> anyhow::Result<bool> {
         #[allow(clippy::redundant_pattern_matching)]
-        let url = format!("{}/labels/{}", self.url(), label);
+        let url = format!("{}/labels/{}", self.url(client), label);
         match client._send_req(client.get(&url)).await {
             Ok((_, _)) => Ok(true),
             Err(e) => {
@@ -431,14 +495,37 @@ impl Issue {
         self.pull_request.is_some()
     }
 
+    /// The commit at the head of the pull request, if this was fetched as a pull request.
+    pub fn head_sha(&self) -> Option<&str> {
+        self.head.as_ref().map(|head| head.sha.as_str())
+    }
+
     pub async fn get_comment(&self, client: &GithubClient, id: usize) -> anyhow::Result<Comment> {
-        let comment_url = format!("{}/issues/comments/{}", self.repository().url(), id);
+        let comment_url = format!("{}/issues/comments/{}", self.repository().url(client), id);
         let comment = client.json(client.get(&comment_url)).await?;
         Ok(comment)
     }
 
+    /// Returns all comments on this issue, oldest first.
+    pub async fn get_comments(&self, client: &GithubClient) -> anyhow::Result<Vec<Comment>> {
+        let mut comments = Vec::new();
+        for page in 1.. {
+            let url = format!("{}?per_page=100&page={}", self.comments_url, page);
+            let page: Vec<Comment> = client
+                .json(client.get(&url))
+                .await
+                .context("failed to list comments")?;
+            let last = page.len() < 100;
+            comments.extend(page);
+            if last {
+                break;
+            }
+        }
+        Ok(comments)
+    }
+
     pub async fn edit_body(&self, client: &GithubClient, body: &str) -> anyhow::Result<()> {
-        let edit_url = format!("{}/issues/{}", self.repository().url(), self.number);
+        let edit_url = format!("{}/issues/{}", self.repository().url(client), self.number);
         #[derive(serde::Serialize)]
         struct ChangedIssue<'a> {
             body: &'a str,
@@ -456,7 +543,7 @@ impl Issue {
         id: usize,
         new_body: &str,
     ) -> anyhow::Result<()> {
-        let comment_url = format!("{}/issues/comments/{}", self.repository().url(), id);
+        let comment_url = format!("{}/issues/comments/{}", self.repository().url(client), id);
         #[derive(serde::Serialize)]
         struct NewComment<'a> {
             body: &'a str,
@@ -489,7 +576,7 @@ impl Issue {
         // DELETE /repos/:owner/:repo/issues/:number/labels/{name}
         let url = format!(
             "{repo_url}/issues/{number}/labels/{name}",
-            repo_url = self.repository().url(),
+            repo_url = self.repository().url(client),
             number = self.number,
             name = label,
         );
@@ -521,7 +608,7 @@ impl Issue {
         // repo_url = https://api.github.com/repos/Codertocat/Hello-World
         let url = format!(
             "{repo_url}/issues/{number}/labels",
-            repo_url = self.repository().url(),
+            repo_url = self.repository().url(client),
             number = self.number
         );
 
@@ -581,7 +668,7 @@ impl Issue {
         log::info!("remove {:?} assignees for {}", selection, self.global_id());
         let url = format!(
             "{repo_url}/issues/{number}/assignees",
-            repo_url = self.repository().url(),
+            repo_url = self.repository().url(client),
             number = self.number
         );
 
@@ -621,7 +708,7 @@ impl Issue {
         log::info!("add_assignee {} for {}", user, self.global_id());
         let url = format!(
             "{repo_url}/issues/{number}/assignees",
-            repo_url = self.repository().url(),
+            repo_url = self.repository().url(client),
             number = self.number
         );
 
@@ -630,10 +717,13 @@ impl Issue {
             assignees: &'a [&'a str],
         }
 
-        let result: Issue = client
-            .json(client.post(&url).json(&AssigneeReq { assignees: &[user] }))
-            .await
-            .map_err(AssignmentError::Http)?;
+        let req = client.post(&url).json(&AssigneeReq { assignees: &[user] });
+        if client.is_dry_run() {
+            // There is no updated issue to check the assignees against.
+            client._send_req(req).await.map_err(AssignmentError::Http)?;
+            return Ok(());
+        }
+        let result: Issue = client.json(req).await.map_err(AssignmentError::Http)?;
         // Invalid assignees are silently ignored. We can just check if the user is now
         // contained in the assignees list.
         let success = result.assignees.iter().any(|u| u.login.as_str() == user);
@@ -664,7 +754,17 @@ impl Issue {
             title
         );
 
-        let create_url = format!("{}/milestones", self.repository().url());
+        let create_url = format!("{}/milestones", self.repository().url(client));
+        let url = format!("{}/issues/{}", self.repository().url(client), self.number);
+        if client.is_dry_run() {
+            // The milestone is not actually created, so its number can't be looked up;
+            // the planned update names it by its title instead.
+            let create = serde_json::to_string(&MilestoneCreateBody { title }).unwrap();
+            client.record_planned("POST", &create_url, Some(create));
+            let update = serde_json::json!({ "milestone": title }).to_string();
+            client.record_planned("PATCH", &url, Some(update));
+            return Ok(());
+        }
         let resp = client
             .send_req(
                 client
@@ -676,7 +776,7 @@ impl Issue {
         // fine, it just means the milestone was already created.
         log::trace!("Created milestone: {:?}", resp);
 
-        let list_url = format!("{}/milestones", self.repository().url());
+        let list_url = format!("{}/milestones", self.repository().url(client));
         let milestone_list: Vec<Milestone> = client.json(client.get(&list_url)).await?;
         let milestone_no = if let Some(milestone) = milestone_list.iter().find(|v| v.title == title)
         {
@@ -693,7 +793,6 @@ impl Issue {
         struct SetMilestone {
             milestone: u64,
         }
-        let url = format!("{}/issues/{}", self.repository().url(), self.number);
         client
             ._send_req(client.patch(&url).json(&SetMilestone {
                 milestone: milestone_no,
@@ -704,7 +803,7 @@ impl Issue {
     }
 
     pub async fn close(&self, client: &GithubClient) -> anyhow::Result<()> {
-        let edit_url = format!("{}/issues/{}", self.repository().url(), self.number);
+        let edit_url = format!("{}/issues/{}", self.repository().url(client), self.number);
         #[derive(serde::Serialize)]
         struct CloseIssue<'a> {
             state: &'a str,
@@ -730,7 +829,7 @@ impl Issue {
 
         let mut req = client.get(&format!(
             "{}/compare/{}...{}",
-            self.repository().url(),
+            self.repository().url(client),
             before,
             after
         ));
@@ -738,6 +837,38 @@ impl Issue {
         let diff = client.send_req(req).await?;
         Ok(Some(String::from(String::from_utf8_lossy(&diff))))
     }
+
+    /// Returns the paths of the files changed by this pull request, including the old
+    /// paths of renamed files.
+    pub async fn files(&self, client: &GithubClient) -> anyhow::Result<Vec<String>> {
+        #[derive(serde::Deserialize)]
+        struct PullRequestFile {
+            filename: String,
+            previous_filename: Option<String>,
+        }
+        let mut files = Vec::new();
+        for page in 1.. {
+            let url = format!(
+                "{}/pulls/{}/files?per_page=100&page={}",
+                self.repository().url(client),
+                self.number,
+                page
+            );
+            let page: Vec<PullRequestFile> = client
+                .json(client.get(&url))
+                .await
+                .context("failed to list changed files")?;
+            let last = page.len() < 100;
+            files.extend(
+                page.into_iter()
+                    .flat_map(|file| std::iter::once(file.filename).chain(file.previous_filename)),
+            );
+            if last {
+                break;
+            }
+        }
+        Ok(files)
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -841,6 +972,16 @@ pub struct IssuesEvent {
     pub repository: Repository,
     /// Some if action is IssuesAction::Labeled, for example
     pub label: Option<Label>,
+    /// Some if action is IssuesAction::ReviewRequested for a user
+    pub requested_reviewer: Option<User>,
+    /// Some if action is IssuesAction::ReviewRequested for a team
+    pub requested_team: Option<RequestedTeam>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RequestedTeam {
+    pub name: String,
+    pub slug: String,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -876,6 +1017,8 @@ pub struct IssueSearchResult {
 #[derive(Debug, serde::Deserialize)]
 pub struct Repository {
     pub full_name: String,
+    #[serde(default)]
+    pub default_branch: Option<String>,
 }
 
 #[derive(Copy, Clone)]
@@ -887,9 +1030,6 @@ struct Ordering<'a> {
 }
 
 impl Repository {
-    const GITHUB_API_URL: &'static str = "https://api.github.com";
-    const GITHUB_GRAPHQL_API_URL: &'static str = "https://api.github.com/graphql";
-
     pub fn owner(&self) -> &str {
         self.full_name.split_once('/').unwrap().0
     }
@@ -898,6 +1038,84 @@ impl Repository {
         self.full_name.split_once('/').unwrap().1
     }
 
+    fn url(&self, client: &GithubClient) -> String {
+        format!("{}/repos/{}", client.api_url(), self.full_name)
+    }
+
+    pub async fn get_pr(&self, client: &GithubClient, number: u64) -> anyhow::Result<Issue> {
+        let url = format!("{}/pulls/{}", self.url(client), number);
+        client
+            .json(client.get(&url))
+            .await
+            .with_context(|| format!("failed to get PR #{} of {}", number, self.full_name))
+    }
+
+    /// Returns the numbers of the open pull requests whose head is `sha`.
+    ///
+    /// Unlike the pull requests listed in CI events, this also finds pull requests
+    /// from forks.
+    pub async fn prs_for_commit(
+        &self,
+        client: &GithubClient,
+        sha: &str,
+    ) -> anyhow::Result<Vec<u64>> {
+        let url = format!(
+            "{}/search/issues?q=repo:{}+is:pr+is:open+{}",
+            client.api_url(),
+            self.full_name,
+            sha
+        );
+        let result = client
+            .json::<IssueSearchResult>(client.get(&url))
+            .await
+            .with_context(|| format!("failed to search PRs for {}", sha))?;
+        Ok(result.items.iter().map(|pr| pr.number).collect())
+    }
+
+    /// Returns the jobs of a GitHub Actions workflow run.
+    pub async fn workflow_run_jobs(
+        &self,
+        client: &GithubClient,
+        run_id: u64,
+    ) -> anyhow::Result<Vec<CheckRun>> {
+        #[derive(serde::Deserialize)]
+        struct Jobs {
+            jobs: Vec<CheckRun>,
+        }
+        let url = format!(
+            "{}/actions/runs/{}/jobs?per_page=100",
+            self.url(client),
+            run_id
+        );
+        let jobs: Jobs = client
+            .json(client.get(&url))
+            .await
+            .with_context(|| format!("failed to list jobs of workflow run {}", run_id))?;
+        Ok(jobs.jobs)
+    }
+
+    /// Returns the check runs of a check suite.
+    pub async fn check_suite_runs(
+        &self,
+        client: &GithubClient,
+        suite_id: u64,
+    ) -> anyhow::Result<Vec<CheckRun>> {
+        #[derive(serde::Deserialize)]
+        struct CheckRuns {
+            check_runs: Vec<CheckRun>,
+        }
+        let url = format!(
+            "{}/check-suites/{}/check-runs?per_page=100",
+            self.url(client),
+            suite_id
+        );
+        let runs: CheckRuns = client
+            .json(client.get(&url))
+            .await
+            .with_context(|| format!("failed to list check runs of suite {}", suite_id))?;
+        Ok(runs.check_runs)
+    }
+
     pub async fn get_issues<'a>(
         &self,
         client: &GithubClient,
@@ -949,11 +1167,17 @@ impl Repository {
         let mut issues = vec![];
         loop {
             let url = if use_search_api {
-                self.build_search_issues_url(&filters, include_labels, exclude_labels, ordering)
+                self.build_search_issues_url(
+                    client,
+                    &filters,
+                    include_labels,
+                    exclude_labels,
+                    ordering,
+                )
             } else if is_pr {
-                self.build_pulls_url(&filters, include_labels, ordering)
+                self.build_pulls_url(client, &filters, include_labels, ordering)
             } else {
-                self.build_issues_url(&filters, include_labels, ordering)
+                self.build_issues_url(client, &filters, include_labels, ordering)
             };
 
             let result = client.get(&url);
@@ -982,24 +1206,27 @@ impl Repository {
 
     fn build_issues_url(
         &self,
+        client: &GithubClient,
         filters: &Vec<(&str, &str)>,
         include_labels: &Vec<&str>,
         ordering: Ordering<'_>,
     ) -> String {
-        self.build_endpoint_url("issues", filters, include_labels, ordering)
+        self.build_endpoint_url(client, "issues", filters, include_labels, ordering)
     }
 
     fn build_pulls_url(
         &self,
+        client: &GithubClient,
         filters: &Vec<(&str, &str)>,
         include_labels: &Vec<&str>,
         ordering: Ordering<'_>,
     ) -> String {
-        self.build_endpoint_url("pulls", filters, include_labels, ordering)
+        self.build_endpoint_url(client, "pulls", filters, include_labels, ordering)
     }
 
     fn build_endpoint_url(
         &self,
+        client: &GithubClient,
         endpoint: &str,
         filters: &Vec<(&str, &str)>,
         include_labels: &Vec<&str>,
@@ -1022,7 +1249,7 @@ impl Repository {
             .join("&");
         format!(
             "{}/repos/{}/{}?{}",
-            Repository::GITHUB_API_URL,
+            client.api_url(),
             self.full_name,
             endpoint,
             filters
@@ -1031,6 +1258,7 @@ impl Repository {
 
     fn build_search_issues_url(
         &self,
+        client: &GithubClient,
         filters: &Vec<(&str, &str)>,
         include_labels: &Vec<&str>,
         exclude_labels: &Vec<&str>,
@@ -1055,7 +1283,7 @@ impl Repository {
             .join("+");
         format!(
             "{}/search/issues?q={}&sort={}&order={}&per_page={}&page={}",
-            Repository::GITHUB_API_URL,
+            client.api_url(),
             filters,
             ordering.sort,
             ordering.direction,
@@ -1178,34 +1406,202 @@ pub struct CreateEvent {
 pub struct PushEvent {
     #[serde(rename = "ref")]
     pub git_ref: String,
+    #[serde(default)]
+    pub commits: Vec<PushCommit>,
     repository: Repository,
     sender: User,
 }
 
+#[derive(Debug, serde::Deserialize)]
+pub struct PushCommit {
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+impl PushEvent {
+    /// Whether this push is to the default branch of the repository.
+    pub fn is_to_default_branch(&self) -> bool {
+        match (
+            &self.repository.default_branch,
+            self.git_ref.strip_prefix("refs/heads/"),
+        ) {
+            (Some(default), Some(branch)) => default == branch,
+            _ => false,
+        }
+    }
+
+    /// Whether any of the pushed commits adds, removes or modifies `path`.
+    pub fn touches(&self, path: &str) -> bool {
+        self.commits.iter().any(|commit| {
+            commit
+                .added
+                .iter()
+                .chain(&commit.removed)
+                .chain(&commit.modified)
+                .any(|p| p == path)
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckRunAction {
+    Created,
+    Completed,
+    Rerequested,
+    RequestedAction,
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckSuiteAction {
+    Completed,
+    Requested,
+    Rerequested,
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowRunAction {
+    Requested,
+    InProgress,
+    Completed,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckConclusion {
+    Success,
+    Failure,
+    Neutral,
+    Cancelled,
+    Skipped,
+    TimedOut,
+    ActionRequired,
+    Stale,
+    StartupFailure,
+}
+
+impl CheckConclusion {
+    /// Whether this conclusion means that the checked code is broken.
+    pub fn is_failure(self) -> bool {
+        matches!(
+            self,
+            CheckConclusion::Failure | CheckConclusion::TimedOut | CheckConclusion::StartupFailure
+        )
+    }
+}
+
+/// A pull request as listed in check runs, check suites and workflow runs.
+///
+/// GitHub leaves these lists empty for pull requests from forks, see
+/// `Repository::prs_for_commit`.
+#[derive(Debug, serde::Deserialize)]
+pub struct CiPullRequest {
+    pub number: u64,
+}
+
+/// A check run, or a job of a GitHub Actions workflow run.
+#[derive(Debug, serde::Deserialize)]
+pub struct CheckRun {
+    pub id: u64,
+    pub name: String,
+    pub head_sha: String,
+    pub conclusion: Option<CheckConclusion>,
+    pub html_url: Option<String>,
+    #[serde(default)]
+    pub pull_requests: Vec<CiPullRequest>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CheckRunEvent {
+    pub action: CheckRunAction,
+    pub check_run: CheckRun,
+    pub repository: Repository,
+    sender: User,
+}
+
+/// The GitHub App which created a check suite.
+#[derive(Debug, serde::Deserialize)]
+pub struct CheckSuiteApp {
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CheckSuite {
+    pub id: u64,
+    pub head_sha: String,
+    pub conclusion: Option<CheckConclusion>,
+    pub app: CheckSuiteApp,
+    pub pull_requests: Vec<CiPullRequest>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CheckSuiteEvent {
+    pub action: CheckSuiteAction,
+    pub check_suite: CheckSuite,
+    pub repository: Repository,
+    sender: User,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    pub name: String,
+    pub head_sha: String,
+    pub conclusion: Option<CheckConclusion>,
+    pub html_url: String,
+    pub pull_requests: Vec<CiPullRequest>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct WorkflowRunEvent {
+    pub action: WorkflowRunAction,
+    pub workflow_run: WorkflowRun,
+    pub repository: Repository,
+    sender: User,
+}
+
 #[derive(Debug)]
 pub enum Event {
     Create(CreateEvent),
     IssueComment(IssueCommentEvent),
     Issue(IssuesEvent),
     Push(PushEvent),
+    CheckRun(CheckRunEvent),
+    CheckSuite(CheckSuiteEvent),
+    WorkflowRun(WorkflowRunEvent),
 }
 
 impl Event {
-    pub fn repo_name(&self) -> String {
+    pub fn repository(&self) -> &Repository {
         match self {
-            Event::Create(event) => event.repository.full_name.clone(),
-            Event::IssueComment(event) => event.repository.full_name.clone(),
-            Event::Issue(event) => event.repository.full_name.clone(),
-            Event::Push(event) => event.repository.full_name.clone(),
+            Event::Create(event) => &event.repository,
+            Event::IssueComment(event) => &event.repository,
+            Event::Issue(event) => &event.repository,
+            Event::Push(event) => &event.repository,
+            Event::CheckRun(event) => &event.repository,
+            Event::CheckSuite(event) => &event.repository,
+            Event::WorkflowRun(event) => &event.repository,
         }
     }
 
+    pub fn repo_name(&self) -> String {
+        self.repository().full_name.clone()
+    }
+
     pub fn issue(&self) -> Option<&Issue> {
         match self {
             Event::Create(_) => None,
             Event::IssueComment(event) => Some(&event.issue),
             Event::Issue(event) => Some(&event.issue),
             Event::Push(_) => None,
+            Event::CheckRun(_) | Event::CheckSuite(_) | Event::WorkflowRun(_) => None,
         }
     }
 
@@ -1216,6 +1612,7 @@ impl Event {
             Event::Issue(e) => Some(&e.issue.body),
             Event::IssueComment(e) => Some(&e.comment.body),
             Event::Push(_) => None,
+            Event::CheckRun(_) | Event::CheckSuite(_) | Event::WorkflowRun(_) => None,
         }
     }
 
@@ -1226,6 +1623,7 @@ impl Event {
             Event::Issue(e) => Some(&e.changes.as_ref()?.body.as_ref()?.from),
             Event::IssueComment(e) => Some(&e.changes.as_ref()?.body.as_ref()?.from),
             Event::Push(_) => None,
+            Event::CheckRun(_) | Event::CheckSuite(_) | Event::WorkflowRun(_) => None,
         }
     }
 
@@ -1235,6 +1633,9 @@ impl Event {
             Event::Issue(e) => Some(&e.issue.html_url),
             Event::IssueComment(e) => Some(&e.comment.html_url),
             Event::Push(_) => None,
+            Event::CheckRun(e) => e.check_run.html_url.as_deref(),
+            Event::CheckSuite(_) => None,
+            Event::WorkflowRun(e) => Some(&e.workflow_run.html_url),
         }
     }
 
@@ -1244,6 +1645,9 @@ impl Event {
             Event::Issue(e) => &e.issue.user,
             Event::IssueComment(e) => &e.comment.user,
             Event::Push(e) => &e.sender,
+            Event::CheckRun(e) => &e.sender,
+            Event::CheckSuite(e) => &e.sender,
+            Event::WorkflowRun(e) => &e.sender,
         }
     }
 
@@ -1253,6 +1657,7 @@ impl Event {
             Event::Issue(e) => Some(e.issue.created_at.into()),
             Event::IssueComment(e) => Some(e.comment.updated_at.into()),
             Event::Push(_) => None,
+            Event::CheckRun(_) | Event::CheckSuite(_) | Event::WorkflowRun(_) => None,
         }
     }
 }
@@ -1299,35 +1704,227 @@ fn get_token_from_git_config() -> anyhow::Result<String> {
     Ok(git_token)
 }
 
+/// A write request which was not sent because of the dry-run mode.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PlannedAction {
+    pub method: String,
+    pub url: String,
+    pub body: Option<String>,
+}
+
+tokio::task_local! {
+    static PLANNED_ACTIONS: RefCell<Vec<PlannedAction>>;
+}
+
+/// Runs `fut` in dry-run mode, returning its output and the write requests that were
+/// skipped meanwhile.
+///
+/// This works with any `GithubClient`; clients in dry-run mode (see
+/// `GithubClient::with_dry_run`) skip writes even outside of this function, but only
+/// record them inside of it.
+pub async fn dry_run<F: Future>(fut: F) -> (F::Output, Vec<PlannedAction>) {
+    PLANNED_ACTIONS
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = fut.await;
+            (output, PLANNED_ACTIONS.with(|actions| actions.take()))
+        })
+        .await
+}
+
+/// Base URLs of the GitHub instance to talk to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GithubUrls {
+    /// The REST API, e.g. `https://github.example.com/api/v3` on GitHub Enterprise.
+    pub api: String,
+    /// The GraphQL endpoint, e.g. `https://github.example.com/api/graphql`.
+    pub graphql: String,
+    /// Raw file contents, e.g. `https://github.example.com/raw`.
+    pub raw: String,
+}
+
+impl Default for GithubUrls {
+    fn default() -> Self {
+        GithubUrls {
+            api: String::from("https://api.github.com"),
+            graphql: String::from("https://api.github.com/graphql"),
+            raw: String::from("https://raw.githubusercontent.com"),
+        }
+    }
+}
+
+impl GithubUrls {
+    /// Reads the URLs from the `GITHUB_API_URL`, `GITHUB_GRAPHQL_URL` and `GITHUB_RAW_URL`
+    /// environment variables, defaulting to github.com.
+    ///
+    /// If only `GITHUB_API_URL` is set, the GraphQL endpoint defaults to `<api>/graphql`.
+    pub fn from_env() -> Self {
+        let default = GithubUrls::default();
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(|v: String| v.trim_end_matches('/').to_owned())
+        };
+        let api = var("GITHUB_API_URL");
+        GithubUrls {
+            graphql: var("GITHUB_GRAPHQL_URL")
+                .or_else(|| api.as_ref().map(|api| format!("{}/graphql", api)))
+                .unwrap_or(default.graphql),
+            raw: var("GITHUB_RAW_URL").unwrap_or(default.raw),
+            api: api.unwrap_or(default.api),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct GithubClient {
     token: String,
     client: Client,
+    dry_run: bool,
+    urls: GithubUrls,
+    app: Option<Arc<app::GithubApp>>,
 }
 
 impl GithubClient {
+    /// Creates a client talking to the GitHub instance configured in the environment,
+    /// see `GithubUrls::from_env`.
     pub fn new(client: Client, token: String) -> Self {
-        GithubClient { client, token }
+        GithubClient {
+            client,
+            token,
+            dry_run: false,
+            urls: GithubUrls::from_env(),
+            app: None,
+        }
+    }
+
+    /// Authenticates as a GitHub App. Use `for_repo` to get a client with the token of
+    /// the installation covering a repository; other requests use the token passed to
+    /// `new`.
+    pub fn with_app(mut self, app: app::GithubApp) -> Self {
+        self.app = Some(Arc::new(app));
+        self
+    }
+
+    pub fn is_app(&self) -> bool {
+        self.app.is_some()
+    }
+
+    /// Returns a client authenticated for `repo` (`owner/name`). When running as a
+    /// GitHub App, this is the installation token for the repository's owner;
+    /// otherwise the client is returned unchanged.
+    pub async fn for_repo(&self, repo: &str) -> anyhow::Result<GithubClient> {
+        let mut client = self.clone();
+        if let Some(app) = &self.app {
+            client.token = app.installation_token(self, repo).await?;
+        }
+        Ok(client)
+    }
+
+    pub fn with_urls(mut self, urls: GithubUrls) -> Self {
+        self.urls = urls;
+        self
+    }
+
+    /// Base URL of the REST API, without a trailing slash.
+    pub fn api_url(&self) -> &str {
+        &self.urls.api
+    }
+
+    pub fn graphql_url(&self) -> &str {
+        &self.urls.graphql
+    }
+
+    /// Base URL for raw file contents, without a trailing slash.
+    pub fn raw_url(&self) -> &str {
+        &self.urls.raw
     }
 
+    /// Creates a client with the default token, in dry-run mode if the
+    /// `GITHUB_DRY_RUN` environment variable is set.
+    ///
+    /// If `GITHUB_APP_ID` is set, the client authenticates as that GitHub App (see
+    /// `app::GithubApp::from_env`), and `GITHUB_API_TOKEN` becomes optional.
     pub fn new_with_default_token(client: Client) -> Self {
-        Self::new(client, default_token_from_env())
+        let dry_run = std::env::var_os("GITHUB_DRY_RUN").map_or(false, |v| !v.is_empty());
+        let gh = match app::GithubApp::from_env().expect("invalid GitHub App configuration") {
+            Some(app) => {
+                let token = std::env::var("GITHUB_API_TOKEN").unwrap_or_default();
+                Self::new(client, token).with_app(app)
+            }
+            None => Self::new(client, default_token_from_env()),
+        };
+        gh.with_dry_run(dry_run)
+    }
+
+    /// In dry-run mode, only GET requests are sent; other requests are logged and
+    /// answered with an empty JSON object.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Whether write requests are skipped, either because this client is in dry-run
+    /// mode or because we are running inside `dry_run`.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run || PLANNED_ACTIONS.try_with(|_| ()).is_ok()
+    }
+
+    /// Records a write which was skipped because of the dry-run mode. This is also used
+    /// by handlers for requests which go through octocrab.
+    pub fn record_planned(&self, method: &str, url: &str, body: Option<String>) {
+        log::info!("dry run: not sending {} {}", method, url);
+        let action = PlannedAction {
+            method: method.to_owned(),
+            url: url.to_owned(),
+            body,
+        };
+        let _ = PLANNED_ACTIONS.try_with(|actions| actions.borrow_mut().push(action));
+    }
+
+    /// Checks that GitHub accepts our credentials. This uses the `/rate_limit`
+    /// endpoint, which does not count against the rate limit.
+    pub async fn check_auth(&self) -> anyhow::Result<()> {
+        if let Some(app) = &self.app {
+            return app.check(self).await;
+        }
+        let _: serde_json::Value = self
+            .json(self.get(&format!("{}/rate_limit", self.api_url())))
+            .await?;
+        Ok(())
+    }
+
+    /// Builds an octocrab instance with the same token and GitHub instance as this client.
+    pub fn octocrab(&self) -> anyhow::Result<octocrab::Octocrab> {
+        // Relative paths are joined onto the base URL, so it needs a trailing slash.
+        Ok(octocrab::OctocrabBuilder::new()
+            .personal_token(self.token.clone())
+            .base_url(format!("{}/", self.api_url()))?
+            .build()?)
     }
 
     pub fn raw(&self) -> &Client {
         &self.client
     }
 
+    /// Returns the name of the default branch of `repo` (`owner/name`).
+    pub async fn default_branch(&self, repo: &str) -> anyhow::Result<String> {
+        let repository: Repository = self
+            .json(self.get(&format!("{}/repos/{}", self.api_url(), repo)))
+            .await
+            .with_context(|| format!("failed to get repository {}", repo))?;
+        repository
+            .default_branch
+            .with_context(|| format!("repository {} has no default branch", repo))
+    }
+
     pub async fn raw_file(
         &self,
         repo: &str,
         branch: &str,
         path: &str,
     ) -> anyhow::Result<Option<Vec<u8>>> {
-        let url = format!(
-            "https://raw.githubusercontent.com/{}/{}/{}",
-            repo, branch, path
-        );
+        let url = format!("{}/{}/{}/{}", self.raw_url(), repo, branch, path);
         let req = self.get(&url);
         let req_dbg = format!("{:?}", req);
         let req = req
@@ -1395,7 +1992,8 @@ impl GithubClient {
 
     pub async fn rust_commit(&self, sha: &str) -> Option<GithubCommit> {
         let req = self.get(&format!(
-            "https://api.github.com/repos/rust-lang/rust/commits/{}",
+            "{}/repos/rust-lang/rust/commits/{}",
+            self.api_url(),
             sha
         ));
         match self.json(req).await {
@@ -1409,7 +2007,10 @@ impl GithubClient {
 
     /// This does not retrieve all of them, only the last several.
     pub async fn bors_commits(&self) -> Vec<GithubCommit> {
-        let req = self.get("https://api.github.com/repos/rust-lang/rust/commits?author=bors");
+        let req = self.get(&format!(
+            "{}/repos/rust-lang/rust/commits?author=bors",
+            self.api_url()
+        ));
         match self.json(req).await {
             Ok(r) => r,
             Err(e) => {
@@ -1458,6 +2059,51 @@ pub trait IssuesQuery {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn set_milestone_dry_run() {
+        let issue: Issue = serde_json::from_value(serde_json::json!({
+            "number": 1,
+            "body": "",
+            "created_at": "2021-01-01T00:00:00Z",
+            "updated_at": "2021-01-01T00:00:00Z",
+            "title": "Test",
+            "html_url": "https://github.com/o/r/pull/1",
+            "user": {"login": "author", "id": 1},
+            "labels": [],
+            "assignees": [],
+            "comments_url": "https://api.github.com/repos/o/r/issues/1/comments"
+        }))
+        .unwrap();
+        // Requests which are sent fail, as there is no proxy on this port.
+        let http = Client::builder()
+            .proxy(reqwest::Proxy::all("http://127.0.0.1:1").unwrap())
+            .build()
+            .unwrap();
+        let client = GithubClient::new(http, String::from("token"));
+        let (result, planned) = dry_run(issue.set_milestone(&client, "1.60.0")).await;
+        result.unwrap();
+        let planned = planned
+            .iter()
+            .map(|a| (a.method.as_str(), a.url.as_str(), a.body.as_deref()))
+            .collect::<Vec<_>>();
+        let repo = format!("{}/repos/o/r", client.api_url());
+        assert_eq!(
+            planned,
+            [
+                (
+                    "POST",
+                    &*format!("{}/milestones", repo),
+                    Some(r#"{"title":"1.60.0"}"#)
+                ),
+                (
+                    "PATCH",
+                    &*format!("{}/issues/1", repo),
+                    Some(r#"{"milestone":"1.60.0"}"#)
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn display_labels() {
         let x = UnknownLabels { labels: vec!["A-bootstrap".into(), "xxx".into()] };
@@ -1528,4 +2174,26 @@ index c58310947d2..3b0854d4a9b 100644
             ]
         )
     }
+    #[test]
+    fn push_touching_config() {
+        let push: PushEvent = serde_json::from_value(serde_json::json!({
+            "ref": "refs/heads/main",
+            "commits": [
+                { "added": [], "removed": [], "modified": ["README.md"] },
+                { "added": ["triagebot.toml"], "removed": [], "modified": [] },
+            ],
+            "repository": { "full_name": "rust-lang/triagebot", "default_branch": "main" },
+            "sender": { "login": "ferris", "id": 1 },
+        }))
+        .unwrap();
+        assert!(push.is_to_default_branch());
+        assert!(push.touches("triagebot.toml"));
+        assert!(!push.touches("Cargo.toml"));
+
+        let push = PushEvent {
+            git_ref: "refs/heads/master".to_owned(),
+            ..push
+        };
+        assert!(!push.is_to_default_branch());
+    }
 }
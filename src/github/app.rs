@@ -0,0 +1,185 @@
+//! Authentication as a GitHub App.
+//!
+//! The app signs a JWT with its private key, and exchanges it for an access token of
+//! the installation covering the repository at hand. Installation tokens are valid
+//! for an hour and cached per account until shortly before they expire.
+
+use super::GithubClient;
+use anyhow::Context as _;
+use chrono::{DateTime, Duration, Utc};
+use openssl::{hash::MessageDigest, pkey::PKey, pkey::Private, sign::Signer};
+use reqwest::header::{ACCEPT, AUTHORIZATION, USER_AGENT};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing as log;
+
+pub struct GithubApp {
+    app_id: u64,
+    key: PKey<Private>,
+    // account (organization or user) -> installation token
+    tokens: Mutex<HashMap<String, InstallationToken>>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl GithubApp {
+    pub fn new(app_id: u64, private_key_pem: &[u8]) -> anyhow::Result<GithubApp> {
+        Ok(GithubApp {
+            app_id,
+            key: PKey::private_key_from_pem(private_key_pem)
+                .context("parsing GitHub App private key")?,
+            tokens: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Reads the app configuration from `GITHUB_APP_ID` and `GITHUB_APP_PRIVATE_KEY`
+    /// (the PEM-encoded key; literal `\n` sequences are turned into newlines).
+    pub fn from_env() -> anyhow::Result<Option<GithubApp>> {
+        let app_id = match std::env::var("GITHUB_APP_ID") {
+            Ok(id) if !id.is_empty() => id.parse().context("parsing GITHUB_APP_ID")?,
+            _ => return Ok(None),
+        };
+        let key = std::env::var("GITHUB_APP_PRIVATE_KEY")
+            .context("GITHUB_APP_PRIVATE_KEY must be set along with GITHUB_APP_ID")?
+            .replace("\\n", "\n");
+        Ok(Some(GithubApp::new(app_id, key.as_bytes())?))
+    }
+
+    /// Creates the JWT authenticating as the app itself.
+    fn jwt(&self) -> anyhow::Result<String> {
+        let now = Utc::now().timestamp();
+        let header = base64url(br#"{"alg":"RS256","typ":"JWT"}"#);
+        let claims = serde_json::json!({
+            // Allow for some clock drift.
+            "iat": now - 60,
+            "exp": now + 9 * 60,
+            "iss": self.app_id,
+        });
+        let message = format!("{}.{}", header, base64url(claims.to_string().as_bytes()));
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(message.as_bytes())?;
+        Ok(format!("{}.{}", message, base64url(&signer.sign_to_vec()?)))
+    }
+
+    /// Checks that GitHub accepts the app's credentials.
+    pub async fn check(&self, client: &GithubClient) -> anyhow::Result<()> {
+        let _: serde_json::Value = app_request(
+            client.raw().get(&format!("{}/app", client.api_url())),
+            &self.jwt()?,
+        )
+        .await
+        .context("failed to authenticate as GitHub App")?;
+        Ok(())
+    }
+
+    /// Returns an access token for the installation of the app on `repo` (`owner/name`).
+    pub async fn installation_token(
+        &self,
+        client: &GithubClient,
+        repo: &str,
+    ) -> anyhow::Result<String> {
+        let owner = repo.split('/').next().unwrap_or(repo).to_owned();
+        if let Some(token) = self.tokens.lock().unwrap().get(&owner) {
+            if token.expires_at - Utc::now() > Duration::minutes(5) {
+                return Ok(token.token.clone());
+            }
+        }
+
+        log::debug!("fetching installation token for {}", owner);
+        #[derive(serde::Deserialize)]
+        struct Installation {
+            id: u64,
+        }
+        let jwt = self.jwt()?;
+        let installation: Installation = app_request(
+            client
+                .raw()
+                .get(&format!("{}/repos/{}/installation", client.api_url(), repo)),
+            &jwt,
+        )
+        .await
+        .with_context(|| format!("failed to find the app installation for {}", repo))?;
+        // This is not sent through `GithubClient::post`, as getting a token must also
+        // work in dry-run mode.
+        let token: InstallationToken = app_request(
+            client.raw().post(&format!(
+                "{}/app/installations/{}/access_tokens",
+                client.api_url(),
+                installation.id
+            )),
+            &jwt,
+        )
+        .await
+        .with_context(|| format!("failed to create an installation token for {}", owner))?;
+
+        self.tokens.lock().unwrap().insert(owner, token.clone());
+        Ok(token.token)
+    }
+}
+
+async fn app_request<T: serde::de::DeserializeOwned>(
+    req: reqwest::RequestBuilder,
+    jwt: &str,
+) -> anyhow::Result<T> {
+    Ok(req
+        .header(USER_AGENT, "rust-lang-triagebot")
+        .header(ACCEPT, "application/vnd.github.v3+json")
+        .header(AUTHORIZATION, format!("Bearer {}", jwt))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+fn base64url(data: &[u8]) -> String {
+    openssl::base64::encode_block(data)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::{rsa::Rsa, sign::Verifier};
+
+    #[test]
+    fn base64url_encoding() {
+        assert_eq!(base64url(b"\xfb\xff"), "-_8");
+        assert_eq!(base64url(b"abcd"), "YWJjZA");
+    }
+
+    #[test]
+    fn jwt_is_signed() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let pem = rsa.private_key_to_pem().unwrap();
+        let app = GithubApp::new(1234, &pem).unwrap();
+        let jwt = app.jwt().unwrap();
+
+        let parts = jwt.split('.').collect::<Vec<_>>();
+        assert_eq!(parts.len(), 3);
+        let decode = |s: &str| {
+            let mut s = s.replace('-', "+").replace('_', "/");
+            while s.len() % 4 != 0 {
+                s.push('=');
+            }
+            openssl::base64::decode_block(&s).unwrap()
+        };
+        let claims: serde_json::Value = serde_json::from_slice(&decode(parts[1])).unwrap();
+        assert_eq!(claims["iss"], 1234);
+
+        let public =
+            PKey::from_rsa(Rsa::public_key_from_pem(&rsa.public_key_to_pem().unwrap()).unwrap())
+                .unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &public).unwrap();
+        verifier
+            .update(format!("{}.{}", parts[0], parts[1]).as_bytes())
+            .unwrap();
+        assert!(verifier.verify(&decode(parts[2])).unwrap());
+    }
+}
@@ -181,7 +181,7 @@ impl super::IssuesQuery for LeastRecentlyReviewedPullRequests {
         };
         loop {
             let query = queries::LeastRecentlyReviewedPullRequests::build(&args);
-            let req = client.post(super::Repository::GITHUB_GRAPHQL_API_URL);
+            let req = client.post(client.graphql_url());
             let req = req.json(&query);
 
             let (resp, req_dbg) = client._send_req(req).await?;
@@ -2,8 +2,11 @@ use crate::config::{self, Config, ConfigurationError};
 use crate::github::{Event, GithubClient, IssueCommentAction, IssuesAction, IssuesEvent};
 use octocrab::Octocrab;
 use parser::command::{Command, Input};
+use std::borrow::Cow;
 use std::fmt;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing as log;
 
 #[derive(Debug)]
@@ -25,9 +28,12 @@ impl fmt::Display for HandlerError {
 
 mod assign;
 mod autolabel;
+mod ci_failure;
 mod close;
+mod config_validation;
 mod github_releases;
 mod glacier;
+mod help;
 mod major_change;
 mod milestone_prs;
 mod nominate;
@@ -37,58 +43,82 @@ mod notify_zulip;
 mod ping;
 mod prioritize;
 mod relabel;
+mod review_requests;
 mod review_submitted;
 mod rustc_commits;
 mod shortcut;
 
-pub async fn handle(ctx: &Context, event: &Event) -> Vec<HandlerError> {
-    let config = config::get(&ctx.github, &event.repo_name()).await;
-    let mut errors = Vec::new();
+/// The errors reported by a handler while handling an event.
+pub struct HandlerRun {
+    /// Identifies the handler (or, for commands, the command's position in the comment)
+    /// across the attempts to handle the same event.
+    pub key: String,
+    pub errors: Vec<HandlerError>,
+}
 
-    if let (Ok(config), Event::Issue(event)) = (config.as_ref(), event) {
-        handle_issue(ctx, event, config, &mut errors).await;
+/// Runs the handlers for `event`, except those whose key is in `completed` because
+/// they already ran in an earlier attempt.
+pub async fn handle(ctx: &Context, event: &Event, completed: &[String]) -> Vec<HandlerRun> {
+    if let Event::Push(push) = event {
+        if push.is_to_default_branch() && push.touches(config::CONFIG_FILE_NAME) {
+            config::invalidate(&event.repo_name());
+        }
     }
 
-    if let Some(body) = event.comment_body() {
-        handle_command(ctx, event, &config, body, &mut errors).await;
+    let config = config::get(
+        &ctx.github,
+        &event.repo_name(),
+        event.repository().default_branch.as_deref(),
+    )
+    .await;
+    let mut runs = Vec::new();
+
+    // The handlers depending on the configuration can only run once it is available.
+    if let Err(e @ ConfigurationError::Http(_)) = &config {
+        runs.push(HandlerRun {
+            key: String::from("config"),
+            errors: vec![HandlerError::Other(e.clone().into())],
+        });
     }
 
-    if let Err(e) = notification::handle(ctx, event).await {
-        log::error!(
-            "failed to process event {:?} with notification handler: {:?}",
-            event,
-            e
-        );
+    if let (Ok(config), Event::Issue(event)) = (config.as_ref(), event) {
+        handle_issue(ctx, event, config, completed, &mut runs).await;
     }
 
-    if let Err(e) = rustc_commits::handle(ctx, event).await {
-        log::error!(
-            "failed to process event {:?} with rustc_commits handler: {:?}",
-            event,
-            e
-        );
+    if let Some(body) = event.comment_body() {
+        handle_command(ctx, event, &config, body, completed, &mut runs).await;
     }
 
-    if let Err(e) = milestone_prs::handle(ctx, event).await {
-        log::error!(
-            "failed to process event {:?} with milestone_prs handler: {:?}",
-            event,
-            e
-        );
+    if !matches!(config, Err(ConfigurationError::Http(_))) {
+        let ignore = ignore_options(&config);
+        let notifications = config.as_ref().ok().and_then(|c| c.notifications.as_ref());
+        run_handler("notification", completed, &mut runs, || {
+            notification::handle(ctx, event, notifications, ignore)
+        })
+        .await;
     }
+    run_handler("rustc_commits", completed, &mut runs, || {
+        rustc_commits::handle(ctx, event)
+    })
+    .await;
+    run_handler("milestone_prs", completed, &mut runs, || {
+        milestone_prs::handle(ctx, event)
+    })
+    .await;
+    run_handler("config_validation", completed, &mut runs, || {
+        config_validation::handle(ctx, event)
+    })
+    .await;
 
     if let Some(config) = config
         .as_ref()
         .ok()
         .and_then(|c| c.review_submitted.as_ref())
     {
-        if let Err(e) = review_submitted::handle(ctx, event, config).await {
-            log::error!(
-                "failed to process event {:?} with review_submitted handler: {:?}",
-                event,
-                e
-            )
-        }
+        run_handler("review_submitted", completed, &mut runs, || {
+            review_submitted::handle(ctx, event, config)
+        })
+        .await;
     }
 
     if let Some(ghr_config) = config
@@ -96,43 +126,119 @@ pub async fn handle(ctx: &Context, event: &Event) -> Vec<HandlerError> {
         .ok()
         .and_then(|c| c.github_releases.as_ref())
     {
-        if let Err(e) = github_releases::handle(ctx, event, ghr_config).await {
-            log::error!(
-                "failed to process event {:?} with github_releases handler: {:?}",
-                event,
-                e
-            );
-        }
+        run_handler("github_releases", completed, &mut runs, || {
+            github_releases::handle(ctx, event, ghr_config)
+        })
+        .await;
     }
 
-    errors
+    if let Some(config) = config.as_ref().ok().and_then(|c| c.ci_failure.as_ref()) {
+        run_handler("ci_failure", completed, &mut runs, || {
+            ci_failure::handle(ctx, event, config)
+        })
+        .await;
+    }
+
+    runs
+}
+
+/// Runs a handler which doesn't report errors to users, unless it already completed.
+async fn run_handler<F>(
+    handler: &'static str,
+    completed: &[String],
+    runs: &mut Vec<HandlerRun>,
+    run: impl FnOnce() -> F,
+) where
+    F: Future<Output = anyhow::Result<()>>,
+{
+    if completed.iter().any(|key| key == handler) {
+        log::debug!("skipping {} handler, it already ran", handler);
+        return;
+    }
+    let started = Instant::now();
+    let result = run().await;
+    record_result(handler, started, &result);
+    runs.push(HandlerRun {
+        key: handler.to_owned(),
+        errors: result.err().map(HandlerError::Other).into_iter().collect(),
+    });
+}
+
+/// Records the run of a handler in the metrics, given the errors it reported.
+fn record_handler(handler: &str, started: Instant, errors: &[HandlerError]) {
+    let outcome = if errors.iter().any(|e| matches!(e, HandlerError::Other(_))) {
+        "other"
+    } else if errors.is_empty() {
+        "success"
+    } else {
+        "message"
+    };
+    crate::metrics::record_handler(handler, started, outcome);
+}
+
+fn record_result<T>(handler: &str, started: Instant, result: &anyhow::Result<T>) {
+    let outcome = if result.is_ok() { "success" } else { "other" };
+    crate::metrics::record_handler(handler, started, outcome);
+}
+
+/// A handler, and whether the configuration of a repository enables it.
+pub(crate) struct HandlerInfo {
+    pub(crate) name: &'static str,
+    /// The forms of a command, or a description of what an issue handler reacts to.
+    pub(crate) usage: &'static [&'static str],
+    pub(crate) enabled: bool,
 }
 
 macro_rules! issue_handlers {
-    ($($name:ident,)*) => {
+    ($($name:ident: $description:literal,)*) => {
+        pub(crate) fn issue_handlers(config: &Config) -> Vec<HandlerInfo> {
+            vec![$(HandlerInfo {
+                name: stringify!($name),
+                usage: &[$description],
+                enabled: config.$name.is_some(),
+            },)*]
+        }
+
         async fn handle_issue(
             ctx: &Context,
             event: &IssuesEvent,
             config: &Arc<Config>,
-            errors: &mut Vec<HandlerError>,
+            completed: &[String],
+            runs: &mut Vec<HandlerRun>,
         ) {
             $(
-            match $name::parse_input(ctx, event, config.$name.as_ref()).await {
-                Err(err) => errors.push(HandlerError::Message(err)),
-                Ok(Some(input)) => {
-                    if let Some(config) = &config.$name {
-                        $name::handle_input(ctx, config, event, input).await.unwrap_or_else(|err| errors.push(HandlerError::Other(err)));
-                    } else {
-                        errors.push(HandlerError::Message(format!(
-                            "The feature `{}` is not enabled in this repository.\n\
-                            To enable it add its section in the `triagebot.toml` \
-                            in the root of the repository.",
-                            stringify!($name)
-                        )));
+            if !completed.iter().any(|key| key == stringify!($name)) {
+                let started = Instant::now();
+                let mut errors = Vec::new();
+                let handled = match $name::parse_input(ctx, event, config.$name.as_ref()).await {
+                    Err(err) => {
+                        errors.push(HandlerError::Message(err));
+                        true
+                    }
+                    Ok(Some(input)) => {
+                        if let Some(config) = &config.$name {
+                            $name::handle_input(ctx, config, event, input).await.unwrap_or_else(|err| errors.push(HandlerError::Other(err)));
+                        } else {
+                            errors.push(HandlerError::Message(format!(
+                                "The feature `{}` is not enabled in this repository.\n\
+                                To enable it add its section in the `triagebot.toml` \
+                                in the root of the repository.",
+                                stringify!($name)
+                            )));
+                        }
+                        true
                     }
+                    Ok(None) => false,
+                };
+                if handled {
+                    record_handler(stringify!($name), started, &errors);
+                    runs.push(HandlerRun {
+                        key: stringify!($name).to_owned(),
+                        errors,
+                    });
                 }
-                Ok(None) => {}
-            })*
+            }
+            )*
         }
     }
 }
@@ -140,21 +246,32 @@ macro_rules! issue_handlers {
 // Handle events that happened on issues
 //
 // This is for events that happen only on issues (e.g. label changes).
-// Each module in the list must contain the functions `parse_input` and `handle_input`.
+// Each module in the list must contain the functions `parse_input` and `handle_input`,
+// and is followed by a description of what it reacts to.
 issue_handlers! {
-    autolabel,
-    major_change,
-    notify_zulip,
+    autolabel: "Adds labels when trigger labels are added or trigger files are changed",
+    major_change: "Announces major change proposals on Zulip",
+    notify_zulip: "Posts on Zulip when the configured labels are added or removed",
+    review_requests: "Labels pull requests ready for review and notifies requested reviewers",
 }
 
 macro_rules! command_handlers {
-    ($($name:ident: $enum:ident,)*) => {
+    ($($module:ident::$command:ident => $enum:ident in $name:ident,)*) => {
+        pub(crate) fn command_handlers(config: &Config) -> Vec<HandlerInfo> {
+            vec![$(HandlerInfo {
+                name: stringify!($name),
+                usage: parser::command::usage(stringify!($enum)),
+                enabled: config.$name.is_some(),
+            },)*]
+        }
+
         async fn handle_command(
             ctx: &Context,
             event: &Event,
             config: &Result<Arc<Config>, ConfigurationError>,
             body: &str,
-            errors: &mut Vec<HandlerError>,
+            completed: &[String],
+            runs: &mut Vec<HandlerRun>,
         ) {
             match event {
                 Event::Issue(e) => if !matches!(e.action, IssuesAction::Opened | IssuesAction::Edited) {
@@ -167,15 +284,21 @@ macro_rules! command_handlers {
                     log::debug!("skipping event, comment was {:?}", e.action);
                     return;
                 }
-                Event::Push(_) | Event::Create(_) => {
+                _ => {
                     log::debug!("skipping unsupported event");
                     return;
                 }
             }
 
-            let input = Input::new(&body, vec![&ctx.username, "triagebot"]);
+            let names = ctx.command_names();
+            let names = names.iter().map(|n| n.as_str()).collect::<Vec<_>>();
+            let body = expand_aliases(body, &names, config);
+            let options = ignore_options(config);
+            let input = Input::with_options(&body, names.clone(), options);
             let commands = if let Some(previous) = event.comment_from() {
-                let prev_commands = Input::new(&previous, vec![&ctx.username, "triagebot"]).collect::<Vec<_>>();
+                let previous = expand_aliases(previous, &names, config);
+                let prev_commands = Input::with_options(&previous, names, options)
+                    .collect::<Vec<_>>();
                 input.filter(|cmd| !prev_commands.contains(cmd)).collect::<Vec<_>>()
             } else {
                 input.collect()
@@ -189,19 +312,73 @@ macro_rules! command_handlers {
 
             let config = match config {
                 Ok(config) => config,
-                Err(e @ ConfigurationError::Missing) => {
-                    return errors.push(HandlerError::Message(e.to_string()));
-                }
-                Err(e @ ConfigurationError::Toml(_)) => {
-                    return errors.push(HandlerError::Message(e.to_string()));
-                }
-                Err(e @ ConfigurationError::Http(_)) => {
-                    return errors.push(HandlerError::Other(e.clone().into()));
+                // In repositories not using the bot, it is more likely mentioned in passing.
+                Err(_) if commands.iter().all(|c| matches!(c, Command::Unknown(_))) => return,
+                // Already reported by `handle`.
+                Err(ConfigurationError::Http(_)) => return,
+                Err(_) if completed.iter().any(|key| key == "commands") => return,
+                Err(e) => {
+                    return runs.push(HandlerRun {
+                        key: String::from("commands"),
+                        errors: vec![HandlerError::Message(e.to_string())],
+                    });
                 }
             };
 
-            for command in commands {
-                match command {
+            let mut reported_unknown = false;
+            for (index, command) in commands.into_iter().enumerate() {
+                let key = format!("command-{}", index);
+                if completed.contains(&key) {
+                    reported_unknown |= matches!(command, Command::Unknown(_));
+                    continue;
+                }
+                let started = Instant::now();
+                let mut errors = Vec::new();
+                let handler = match command {
+                    // Help is available whatever the configuration.
+                    Command::Help(Ok(command)) => {
+                        help::handle_command(ctx, config, event, command)
+                            .await
+                            .unwrap_or_else(|err| errors.push(HandlerError::Other(err)));
+                        "help"
+                    }
+                    Command::Help(Err(err)) => {
+                        errors.push(HandlerError::Message(format!(
+                            "Parsing help command in [comment]({}) failed: {}",
+                            event.html_url().expect("has html url"),
+                            err
+                        )));
+                        "help"
+                    }
+                    Command::Unknown(unknown) => {
+                        // A single reply per comment is enough.
+                        if !reported_unknown {
+                            reported_unknown = true;
+                            let hint = match unknown.suggestion {
+                                Some(command) => {
+                                    format!("Did you mean `@{} {}`?", ctx.username, command)
+                                }
+                                None => format!(
+                                    "`@{} help` lists the commands available in this repository.",
+                                    ctx.username
+                                ),
+                            };
+                            errors.push(HandlerError::Message(format!(
+                                "Unknown command `{}`. {}",
+                                unknown.word, hint
+                            )));
+                        }
+                        "unknown"
+                    }
+                    Command::Ambiguous(ambiguous) => {
+                        errors.push(HandlerError::Message(format!(
+                            "The command in [comment]({}) could be read as more than one \
+                            command ({}), so it was not run.",
+                            event.html_url().expect("has html url"),
+                            ambiguous.commands.join(", ")
+                        )));
+                        "ambiguous"
+                    }
                     $(
                     Command::$enum(Ok(command)) => {
                         if let Some(config) = &config.$name {
@@ -216,6 +393,7 @@ macro_rules! command_handlers {
                                 stringify!($name)
                             )));
                         }
+                        stringify!($name)
                     }
                     Command::$enum(Err(err)) => {
                         errors.push(HandlerError::Message(format!(
@@ -224,30 +402,42 @@ macro_rules! command_handlers {
                             event.html_url().expect("has html url"),
                             err
                         )));
+                        stringify!($name)
                     })*
-                }
+                };
+                record_handler(handler, started, &errors);
+                runs.push(HandlerRun { key, errors });
             }
         }
     }
 }
 
+/// The parts of comments which the repository's configuration asks to ignore.
+fn ignore_options(config: &Result<Arc<Config>, ConfigurationError>) -> parser::IgnoreOptions {
+    config
+        .as_ref()
+        .map_or_else(|_| parser::IgnoreOptions::default(), |c| c.ignore_options())
+}
+
+/// Expands the command aliases configured for the repository in `text`.
+fn expand_aliases<'a>(
+    text: &'a str,
+    names: &[&str],
+    config: &Result<Arc<Config>, ConfigurationError>,
+) -> Cow<'a, str> {
+    match config.as_ref().ok().and_then(|c| c.aliases.as_ref()) {
+        Some(aliases) => parser::command::expand_aliases(text, names, &aliases.aliases),
+        None => Cow::Borrowed(text),
+    }
+}
+
 // Handle commands in comments/issues body
 //
-// This is for handlers for commands parsed by the `parser` crate.
-// Each variant of `parser::command::Command` must be in this list,
-// preceded by the module containing the coresponding `handle_command` function
-command_handlers! {
-    assign: Assign,
-    glacier: Glacier,
-    nominate: Nominate,
-    ping: Ping,
-    prioritize: Prioritize,
-    relabel: Relabel,
-    major_change: Second,
-    shortcut: Shortcut,
-    close: Close,
-    note: Note,
-}
+// This is for handlers for commands parsed by the `parser` crate. The commands are
+// listed by `parser::for_each_command!`, along with the section of the configuration
+// enabling each, which is also the module containing its `handle_command` function.
+// `help` is always available. The usage shown in help output comes from the registry.
+parser::for_each_command!(command_handlers);
 
 pub struct Context {
     pub github: GithubClient,
@@ -255,3 +445,50 @@ pub struct Context {
     pub username: String,
     pub octocrab: Octocrab,
 }
+
+/// The GitHub account the bot acts as, from `TRIAGEBOT_USERNAME` (`rustbot` by default).
+pub fn bot_username() -> String {
+    std::env::var("TRIAGEBOT_USERNAME").unwrap_or_else(|_| String::from("rustbot"))
+}
+
+impl Context {
+    /// Names which commands can be addressed to: the bot's username, plus the aliases
+    /// in the comma-separated `TRIAGEBOT_COMMAND_ALIASES` (`triagebot` by default).
+    pub fn command_names(&self) -> Vec<String> {
+        let aliases = std::env::var("TRIAGEBOT_COMMAND_ALIASES")
+            .unwrap_or_else(|_| String::from("triagebot"));
+        std::iter::once(self.username.clone())
+            .chain(
+                aliases
+                    .split(',')
+                    .map(|alias| alias.trim().to_owned())
+                    .filter(|alias| !alias.is_empty()),
+            )
+            .collect()
+    }
+
+    /// Whether `login` is the bot's account: its username, or the `<name>[bot]` account
+    /// it comments as when running as a GitHub App named after it.
+    pub fn is_bot(&self, login: &str) -> bool {
+        let login = login.strip_suffix("[bot]").unwrap_or(login);
+        login.eq_ignore_ascii_case(&self.username)
+    }
+
+    /// Returns a context whose GitHub clients are authenticated for `repo`. This only
+    /// makes a difference when running as a GitHub App, which can be installed on
+    /// several organizations with a separate token for each.
+    pub async fn for_repo(&self, repo: &str) -> anyhow::Result<Context> {
+        let github = self.github.for_repo(repo).await?;
+        let octocrab = if github.is_app() {
+            github.octocrab()?
+        } else {
+            self.octocrab.clone()
+        };
+        Ok(Context {
+            github,
+            db: self.db.clone(),
+            username: self.username.clone(),
+            octocrab,
+        })
+    }
+}
@@ -0,0 +1,220 @@
+//! Purpose: React to failed CI on pull requests.
+//!
+//! When a GitHub Actions workflow run, or the check suite of another CI app, fails on
+//! the head commit of a pull request, the labels configured in the `[ci-failure]`
+//! section are applied, and the author can be pinged with the names of the failed jobs.
+//!
+//! Check suites created by GitHub Actions are skipped, as the corresponding workflow
+//! runs are handled instead.
+
+use crate::{
+    config::CiFailureConfig,
+    github::{
+        CheckConclusion, CheckRun, CheckSuiteAction, CiPullRequest, Event, Label, Repository,
+        WorkflowRunAction,
+    },
+    handlers::Context,
+};
+use std::fmt::Write as _;
+use tracing as log;
+
+/// A failed workflow run or check suite.
+struct Failure<'a> {
+    repo: &'a Repository,
+    /// Name of the workflow, or of the app which ran the check suite.
+    name: &'a str,
+    head_sha: &'a str,
+    html_url: Option<&'a str>,
+    pull_requests: &'a [CiPullRequest],
+    source: Source,
+}
+
+enum Source {
+    WorkflowRun(u64),
+    CheckSuite(u64),
+}
+
+impl Failure<'_> {
+    async fn failed_jobs(&self, ctx: &Context) -> anyhow::Result<Vec<CheckRun>> {
+        let jobs = match self.source {
+            Source::WorkflowRun(id) => self.repo.workflow_run_jobs(&ctx.github, id).await?,
+            Source::CheckSuite(id) => self.repo.check_suite_runs(&ctx.github, id).await?,
+        };
+        Ok(jobs
+            .into_iter()
+            .filter(|job| failed(job.conclusion))
+            .collect())
+    }
+}
+
+fn failed(conclusion: Option<CheckConclusion>) -> bool {
+    conclusion.map_or(false, CheckConclusion::is_failure)
+}
+
+pub(super) async fn handle(
+    ctx: &Context,
+    event: &Event,
+    config: &CiFailureConfig,
+) -> anyhow::Result<()> {
+    let failure = match event {
+        Event::WorkflowRun(e) if e.action == WorkflowRunAction::Completed => {
+            if !failed(e.workflow_run.conclusion) {
+                return Ok(());
+            }
+            Failure {
+                repo: &e.repository,
+                name: &e.workflow_run.name,
+                head_sha: &e.workflow_run.head_sha,
+                html_url: Some(&e.workflow_run.html_url),
+                pull_requests: &e.workflow_run.pull_requests,
+                source: Source::WorkflowRun(e.workflow_run.id),
+            }
+        }
+        Event::CheckSuite(e)
+            if e.action == CheckSuiteAction::Completed
+                && e.check_suite.app.slug != "github-actions" =>
+        {
+            if !failed(e.check_suite.conclusion) {
+                return Ok(());
+            }
+            Failure {
+                repo: &e.repository,
+                name: &e.check_suite.app.name,
+                head_sha: &e.check_suite.head_sha,
+                html_url: None,
+                pull_requests: &e.check_suite.pull_requests,
+                source: Source::CheckSuite(e.check_suite.id),
+            }
+        }
+        _ => return Ok(()),
+    };
+
+    if !config.workflows.is_empty() && !config.workflows.iter().any(|w| w == failure.name) {
+        log::debug!("ignoring failure of {}, not configured", failure.name);
+        return Ok(());
+    }
+
+    let mut pr_numbers = failure
+        .pull_requests
+        .iter()
+        .map(|pr| pr.number)
+        .collect::<Vec<_>>();
+    if pr_numbers.is_empty() {
+        pr_numbers = failure
+            .repo
+            .prs_for_commit(&ctx.github, failure.head_sha)
+            .await?;
+    }
+    if pr_numbers.is_empty() {
+        log::debug!("no pull request for failed commit {}", failure.head_sha);
+        return Ok(());
+    }
+
+    let failed_jobs = if config.ping_author {
+        failure.failed_jobs(ctx).await?
+    } else {
+        Vec::new()
+    };
+
+    for number in pr_numbers {
+        let pr = failure.repo.get_pr(&ctx.github, number).await?;
+        if pr.head_sha() != Some(failure.head_sha) {
+            log::debug!(
+                "skipping CI failure on {}, which was pushed to since",
+                pr.global_id()
+            );
+            continue;
+        }
+
+        for label in &config.remove_labels {
+            pr.remove_label(&ctx.github, label).await?;
+        }
+        if let Some(label) = &config.label {
+            pr.add_labels(
+                &ctx.github,
+                vec![Label {
+                    name: label.clone(),
+                }],
+            )
+            .await?;
+        }
+        if config.ping_author {
+            let comment = failure_comment(&pr.user.login, &failure, &failed_jobs);
+            pr.post_comment(&ctx.github, &comment).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn failure_comment(author: &str, failure: &Failure<'_>, failed_jobs: &[CheckRun]) -> String {
+    let name = match failure.html_url {
+        Some(url) => format!("[{}]({})", failure.name, url),
+        None => failure.name.to_owned(),
+    };
+    let mut comment = format!(
+        "@{} CI ({}) failed on {}.",
+        author,
+        name,
+        &failure.head_sha[..failure.head_sha.len().min(8)]
+    );
+    if !failed_jobs.is_empty() {
+        comment.push_str(" Failed jobs:\n");
+        for job in failed_jobs {
+            match &job.html_url {
+                Some(url) => write!(comment, "\n- [{}]({})", job.name, url).unwrap(),
+                None => write!(comment, "\n- {}", job.name).unwrap(),
+            }
+        }
+    }
+    comment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(name: &str, html_url: Option<&str>) -> CheckRun {
+        CheckRun {
+            id: 1,
+            name: name.to_owned(),
+            head_sha: "0123456789abcdef".to_owned(),
+            conclusion: Some(CheckConclusion::Failure),
+            html_url: html_url.map(|url| url.to_owned()),
+            pull_requests: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn comment_lists_failed_jobs() {
+        let repo = Repository {
+            full_name: "rust-lang/rust".to_owned(),
+            default_branch: None,
+        };
+        let failure = Failure {
+            repo: &repo,
+            name: "CI",
+            head_sha: "0123456789abcdef",
+            html_url: Some("https://example.com/run/1"),
+            pull_requests: &[],
+            source: Source::WorkflowRun(1),
+        };
+        assert_eq!(
+            failure_comment(
+                "ferris",
+                &failure,
+                &[
+                    job("tidy", Some("https://example.com/job/2")),
+                    job("test", None)
+                ]
+            ),
+            "@ferris CI ([CI](https://example.com/run/1)) failed on 01234567. Failed jobs:\n\
+            \n- [tidy](https://example.com/job/2)\
+            \n- test"
+        );
+        assert_eq!(
+            failure_comment("ferris", &failure, &[]),
+            "@ferris CI ([CI](https://example.com/run/1)) failed on 01234567."
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Purpose: Catch mistakes in `triagebot.toml` before they are merged.
+//!
+//! When a pull request changes `triagebot.toml`, the file is read from the head of the
+//! pull request and checked: besides parse errors, unknown keys and invalid label patterns, labels
+//! used by `[autolabel]` must exist in the repository, and teams in `[ping]` must be
+//! in the team data. The file is checked on top of the default configuration of the
+//! organization, if there is one. The problems are listed in a single comment, which
+//! is updated on later pushes.
+
+use crate::{
+    config,
+    github::{self, Event, IssueRepository, IssuesAction},
+    handlers::Context,
+};
+use std::fmt::Write as _;
+use tracing as log;
+
+/// Marks the comment posted by this handler, so that it can be found again.
+const MARKER: &str = "<!-- TRIAGEBOT_CONFIG_VALIDATION -->";
+
+pub(super) async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
+    let e = match event {
+        Event::Issue(e)
+            if e.issue.is_pr()
+                && matches!(
+                    e.action,
+                    IssuesAction::Opened | IssuesAction::Reopened | IssuesAction::Synchronize
+                ) =>
+        {
+            e
+        }
+        _ => return Ok(()),
+    };
+
+    let head_sha = match e.issue.head_sha() {
+        Some(head_sha) => head_sha,
+        None => return Ok(()),
+    };
+    // Listing the changed files is much cheaper than fetching the diff, and most pull
+    // requests don't touch the configuration.
+    let files = e.issue.files(&ctx.github).await?;
+    if !files.iter().any(|file| file == config::CONFIG_FILE_NAME) {
+        return Ok(());
+    }
+
+    // Commits of pull requests from forks can also be read through the base repository.
+    let problems = match ctx
+        .github
+        .raw_file(&e.repository.full_name, head_sha, config::CONFIG_FILE_NAME)
+        .await?
+    {
+        Some(contents) => {
+            let org = config::get_org_config(&ctx.github, &e.repository.full_name).await?;
+            problems(ctx, e.issue.repository(), org.as_deref(), &contents).await?
+        }
+        None => {
+            log::debug!(
+                "{} removes {}",
+                e.issue.global_id(),
+                config::CONFIG_FILE_NAME
+            );
+            Vec::new()
+        }
+    };
+
+    let existing = e
+        .issue
+        .get_comments(&ctx.github)
+        .await?
+        .into_iter()
+        .find(|comment| ctx.is_bot(&comment.user.login) && comment.body.contains(MARKER));
+    let body = comment_body(head_sha, &problems);
+    match existing {
+        Some(comment) if comment.body != body => {
+            e.issue.edit_comment(&ctx.github, comment.id, &body).await?
+        }
+        Some(_) => {}
+        None if !problems.is_empty() => e.issue.post_comment(&ctx.github, &body).await?,
+        None => {}
+    }
+
+    Ok(())
+}
+
+async fn problems(
+    ctx: &Context,
+    repo: &IssueRepository,
+    org: Option<&[u8]>,
+    contents: &[u8],
+) -> anyhow::Result<Vec<String>> {
+    let (config, mut problems) = match config::check(org, contents) {
+        Ok(checked) => checked,
+        Err(e) => return Ok(vec![e.to_string()]),
+    };
+
+    if let Some(autolabel) = &config.autolabel {
+        let mut labels = autolabel
+            .labels
+            .iter()
+            .flat_map(|(label, cfg)| {
+                std::iter::once(label.as_str()).chain(cfg.trigger_labels.iter().map(|l| l.as_str()))
+            })
+            .collect::<Vec<_>>();
+        labels.sort_unstable();
+        labels.dedup();
+        for label in labels {
+            if !repo.has_label(&ctx.github, label).await? {
+                problems.push(format!(
+                    "label `{}` used in `[autolabel]` does not exist in {}",
+                    label, repo
+                ));
+            }
+        }
+    }
+
+    if let Some(ping) = &config.ping {
+        let mut teams = ping.team_names().collect::<Vec<_>>();
+        teams.sort_unstable();
+        for team in teams {
+            if github::get_team(&ctx.github, team).await?.is_none() {
+                problems.push(format!(
+                    "team `{}` in `[ping]` is not in the team data",
+                    team
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+fn comment_body(head_sha: &str, problems: &[String]) -> String {
+    let commit = &head_sha[..head_sha.len().min(8)];
+    let mut body = format!("{}\n", MARKER);
+    if problems.is_empty() {
+        write!(
+            body,
+            "No problems found in `{}` as of {}.",
+            config::CONFIG_FILE_NAME,
+            commit
+        )
+        .unwrap();
+    } else {
+        writeln!(
+            body,
+            "Problems found in `{}` as of {}:",
+            config::CONFIG_FILE_NAME,
+            commit
+        )
+        .unwrap();
+        for problem in problems {
+            // Parse errors span several lines.
+            write!(body, "\n- {}", problem.trim_end().replace('\n', "\n  ")).unwrap();
+        }
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comment_lists_problems() {
+        assert_eq!(
+            comment_body(
+                "0123456789abcdef",
+                &[
+                    "expected an equals, found a newline at line 1 column 6\n".to_owned(),
+                    "team `foo` in `[ping]` is not in the team data".to_owned(),
+                ]
+            ),
+            "<!-- TRIAGEBOT_CONFIG_VALIDATION -->\n\
+             Problems found in `triagebot.toml` as of 01234567:\n\
+             \n- expected an equals, found a newline at line 1 column 6\
+             \n- team `foo` in `[ping]` is not in the team data"
+        );
+        assert_eq!(
+            comment_body("0123456789abcdef", &[]),
+            "<!-- TRIAGEBOT_CONFIG_VALIDATION -->\n\
+             No problems found in `triagebot.toml` as of 01234567."
+        );
+    }
+}
@@ -66,33 +66,35 @@ pub(super) async fn handle(
             if let Some(release) = releases.get(tag) {
                 if release.name != expected_name || release.body != expected_body {
                     log::info!("updating release {} on {}", tag, event.repo_name());
-                    let _: serde_json::Value = ctx
-                        .octocrab
-                        .patch(
-                            &release.url,
-                            Some(&serde_json::json!({
-                                "name": expected_name,
-                                "body": expected_body,
-                            })),
-                        )
-                        .await?;
+                    let body = serde_json::json!({
+                        "name": expected_name,
+                        "body": expected_body,
+                    });
+                    if ctx.github.is_dry_run() {
+                        ctx.github
+                            .record_planned("PATCH", &release.url, Some(body.to_string()));
+                        continue;
+                    }
+                    let _: serde_json::Value =
+                        ctx.octocrab.patch(&release.url, Some(&body)).await?;
                 } else {
                     // Avoid waiting for the delay below.
                     continue;
                 }
             } else {
                 log::info!("creating release {} on {}", tag, event.repo_name());
-                let _: serde_json::Value = ctx
-                    .octocrab
-                    .post(
-                        format!("repos/{}/releases", event.repo_name()),
-                        Some(&serde_json::json!({
-                            "tag_name": tag,
-                            "name": expected_name,
-                            "body": expected_body,
-                        })),
-                    )
-                    .await?;
+                let url = format!("repos/{}/releases", event.repo_name());
+                let body = serde_json::json!({
+                    "tag_name": tag,
+                    "name": expected_name,
+                    "body": expected_body,
+                });
+                if ctx.github.is_dry_run() {
+                    ctx.github
+                        .record_planned("POST", &url, Some(body.to_string()));
+                    continue;
+                }
+                let _: serde_json::Value = ctx.octocrab.post(url, Some(&body)).await?;
             }
 
             log::debug!("sleeping for one second to avoid hitting any rate limit");
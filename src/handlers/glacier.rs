@@ -33,7 +33,9 @@ pub(super) async fn handle_command(
 
     let octocrab = &ctx.octocrab;
 
-    let fork = octocrab.repos("rustbot", "glacier");
+    // The bot's fork of glacier, from `GLACIER_FORK_OWNER` (the bot's account by default).
+    let fork_owner = std::env::var("GLACIER_FORK_OWNER").unwrap_or_else(|_| ctx.username.clone());
+    let fork = octocrab.repos(&fork_owner, "glacier");
     let base = octocrab.repos("rust-lang", "glacier");
 
     let master = base
@@ -47,6 +49,40 @@ pub(super) async fn handle_command(
         unreachable!()
     };
 
+    if ctx.github.is_dry_run() {
+        let branch = format!("triagebot-ice-{}", number);
+        let api = ctx.github.api_url();
+        ctx.github.record_planned(
+            "POST",
+            &format!("{}/repos/{}/glacier/git/refs", api, fork_owner),
+            Some(
+                serde_json::json!({ "ref": format!("refs/heads/{}", branch), "sha": master })
+                    .to_string(),
+            ),
+        );
+        ctx.github.record_planned(
+            "PUT",
+            &format!(
+                "{}/repos/{}/glacier/contents/ices/{}.rs",
+                api, fork_owner, number
+            ),
+            Some(serde_json::json!({ "branch": branch, "content": body }).to_string()),
+        );
+        ctx.github.record_planned(
+            "POST",
+            &format!("{}/repos/rust-lang/glacier/pulls", api),
+            Some(
+                serde_json::json!({
+                    "title": format!("ICE - rust-lang/rust#{}", number),
+                    "head": format!("{}:{}", fork_owner, branch),
+                    "base": "master",
+                })
+                .to_string(),
+            ),
+        );
+        return Ok(());
+    }
+
     fork.create_ref(
         &Reference::Branch(format!("triagebot-ice-{}", number)),
         master,
@@ -65,7 +101,7 @@ pub(super) async fn handle_command(
         .pulls("rust-lang", "glacier")
         .create(
             format!("ICE - rust-lang/rust#{}", number),
-            format!("rustbot:triagebot-ice-{}", number),
+            format!("{}:triagebot-ice-{}", fork_owner, number),
             "master",
         )
         .body(format!(
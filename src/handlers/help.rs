@@ -0,0 +1,153 @@
+//! Purpose: Tell users which commands are available in a repository.
+//!
+//! The reply lists the commands enabled by the repository's configuration and its
+//! command aliases, as well as the teams the user can ping and the labels they can
+//! change.
+//!
+//! Parsing is done in the `parser::command::help` module.
+
+use crate::{
+    config::Config,
+    github::Event,
+    handlers::{self, Context},
+};
+use parser::command::help::HelpCommand;
+use std::fmt::Write as _;
+
+pub(super) async fn handle_command(
+    ctx: &Context,
+    config: &Config,
+    event: &Event,
+    _cmd: HelpCommand,
+) -> anyhow::Result<()> {
+    let is_team_member = event
+        .user()
+        .is_team_member(&ctx.github)
+        .await
+        .unwrap_or(false);
+    let message = help_message(&ctx.username, config, is_team_member);
+    event
+        .issue()
+        .unwrap()
+        .post_comment(&ctx.github, &message)
+        .await
+}
+
+fn help_message(bot: &str, config: &Config, is_team_member: bool) -> String {
+    let mut message = String::from("Commands available in this repository:\n");
+    let forms = handlers::command_handlers(config)
+        .into_iter()
+        .filter(|handler| handler.enabled)
+        .map(|handler| handler.usage)
+        .chain(std::iter::once(parser::command::usage("Help")));
+    for forms in forms {
+        let forms = forms
+            .iter()
+            .map(|form| format!("`@{} {}`", bot, form))
+            .collect::<Vec<_>>();
+        write!(message, "\n- {}", forms.join(", ")).unwrap();
+    }
+    if let Some(aliases) = &config.aliases {
+        let mut aliases = aliases.aliases.iter().collect::<Vec<_>>();
+        aliases.sort();
+        for (alias, expansion) in aliases {
+            write!(
+                message,
+                "\n- `@{} {}`, short for `@{} {}`",
+                bot, alias, bot, expansion
+            )
+            .unwrap();
+        }
+    }
+
+    if let Some(ping) = &config.ping {
+        if is_team_member {
+            let mut teams = ping
+                .team_names()
+                .map(|team| format!("`{}`", team))
+                .collect::<Vec<_>>();
+            teams.sort();
+            write!(message, "\n\nTeams you can ping: {}.", teams.join(", ")).unwrap();
+        }
+    }
+
+    if let Some(relabel) = &config.relabel {
+        if is_team_member {
+            message.push_str("\n\nYou can add and remove any label.");
+        } else {
+            let (denied, allowed): (Vec<_>, Vec<_>) = relabel
+                .allow_unauthenticated
+                .iter()
+                .partition(|pattern| pattern.starts_with('!'));
+            if allowed.is_empty() {
+                message.push_str("\n\nOnly team members can add and remove labels.");
+            } else {
+                let list = |patterns: Vec<&String>| {
+                    patterns
+                        .iter()
+                        .map(|pattern| format!("`{}`", pattern.trim_start_matches('!')))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                write!(
+                    message,
+                    "\n\nLabels you can add and remove: {}",
+                    list(allowed)
+                )
+                .unwrap();
+                if !denied.is_empty() {
+                    write!(message, ", except {}", list(denied)).unwrap();
+                }
+                message.push('.');
+            }
+        }
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_enabled_commands() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [relabel]
+            allow-unauthenticated = ["C-*", "!C-bug"]
+
+            [ping.compiler]
+            message = "Compiler ping"
+
+            [close]
+
+            [aliases]
+            ptal = "ready"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            help_message("rustbot", &config, false),
+            "Commands available in this repository:\n\
+             \n- `@rustbot ping <team>`\
+             \n- `@rustbot label +<label> -<label>`\
+             \n- `@rustbot close`\
+             \n- `@rustbot help`\
+             \n- `@rustbot ptal`, short for `@rustbot ready`\
+             \n\nLabels you can add and remove: `C-*`, except `C-bug`."
+        );
+        assert_eq!(
+            help_message("rustbot", &config, true),
+            "Commands available in this repository:\n\
+             \n- `@rustbot ping <team>`\
+             \n- `@rustbot label +<label> -<label>`\
+             \n- `@rustbot close`\
+             \n- `@rustbot help`\
+             \n- `@rustbot ptal`, short for `@rustbot ready`\
+             \n\nTeams you can ping: `compiler`.\
+             \n\nYou can add and remove any label."
+        );
+    }
+}
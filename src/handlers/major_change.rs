@@ -130,7 +130,7 @@ pub(super) async fn handle_input(
                 content: "The associated GitHub issue has been renamed. Renaming this Zulip topic.",
             };
             let zulip_send_res = zulip_send_req
-                .send(&ctx.github.raw())
+                .send(&ctx.github)
                 .await
                 .context("zulip post failed")?;
 
@@ -143,7 +143,7 @@ pub(super) async fn handle_input(
                 content: None,
             };
             zulip_update_req
-                .send(&ctx.github.raw())
+                .send(&ctx.github)
                 .await
                 .context("zulip message update failed")?;
 
@@ -165,7 +165,7 @@ pub(super) async fn handle_input(
                 content: &breadcrumb_comment,
             };
             zulip_send_breadcrumb_req
-                .send(&ctx.github.raw())
+                .send(&ctx.github)
                 .await
                 .context("zulip post failed")?;
 
@@ -271,7 +271,7 @@ async fn handle(
             .context("post major change comment")?;
     }
 
-    let zulip_req = zulip_req.send(&ctx.github.raw());
+    let zulip_req = zulip_req.send(&ctx.github);
 
     let (gh_res, zulip_res) = futures::join!(github_req, zulip_req);
     zulip_res.context("zulip post failed")?;
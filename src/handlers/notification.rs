@@ -6,6 +6,7 @@
 
 use crate::db::notifications;
 use crate::{
+    config::NotificationsConfig,
     github::{self, Event},
     handlers::Context,
 };
@@ -14,7 +15,12 @@ use std::collections::HashSet;
 use std::convert::{TryFrom, TryInto};
 use tracing as log;
 
-pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
+pub async fn handle(
+    ctx: &Context,
+    event: &Event,
+    config: Option<&NotificationsConfig>,
+    ignore: parser::IgnoreOptions,
+) -> anyhow::Result<()> {
     let body = match event.comment_body() {
         Some(v) => v,
         // Skip events that don't have comment bodies associated
@@ -34,21 +40,32 @@ pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
     let short_description = match event {
         Event::Issue(e) => e.issue.title.clone(),
         Event::IssueComment(e) => format!("Comment on {}", e.issue.title),
-        Event::Push(_) | Event::Create(_) => return Ok(()),
+        _ => return Ok(()),
     };
 
-    let mut caps = parser::get_mentions(body)
+    // Mentions of the bot itself are commands, not pings.
+    let bot_names = ctx.command_names();
+    let mut caps = parser::get_mentions_with(body, ignore)
         .into_iter()
+        .filter(|login| {
+            !bot_names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(login))
+        })
         .collect::<HashSet<_>>();
 
-    // FIXME: Remove this hardcoding. Ideally we need organization-wide
-    // configuration, but it's unclear where to put it.
-    if event.issue().unwrap().repository().organization == "serde-rs" {
-        // Only add dtolnay on new issues/PRs, not on comments to old PRs and
-        // issues.
-        if let Event::Issue(e) = event {
-            if e.action == github::IssuesAction::Opened {
-                caps.insert("dtolnay");
+    // Only notify subscribers of new issues/PRs, not of comments to old PRs
+    // and issues.
+    if let Event::Issue(e) = event {
+        if e.action == github::IssuesAction::Opened {
+            match config {
+                Some(config) => caps.extend(config.subscribers.iter().map(|user| user.as_str())),
+                // serde-rs was notifying dtolnay before subscribers could be
+                // configured, and keeps doing so until it configures its own.
+                None if e.issue.repository().organization == "serde-rs" => {
+                    caps.insert("dtolnay");
+                }
+                None => {}
             }
         }
     }
@@ -57,7 +74,7 @@ pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
     // comment, so they don't get notified again
     let mut users_notified = HashSet::new();
     if let Some(from) = event.comment_from() {
-        for login in parser::get_mentions(from).into_iter() {
+        for login in parser::get_mentions_with(from, ignore).into_iter() {
             if let Some((Ok(users), _)) = id_from_user(ctx, login).await? {
                 users_notified.extend(users.into_iter().map(|user| user.id.unwrap()));
             }
@@ -165,7 +165,7 @@ pub(super) async fn handle_input<'a>(
             },
             content: &msg,
         };
-        zulip_req.send(&ctx.github.raw()).await?;
+        zulip_req.send(&ctx.github).await?;
     }
 
     Ok(())
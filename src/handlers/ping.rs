@@ -41,7 +41,7 @@ pub(super) async fn handle_command(
                 &event.issue().unwrap(),
                 format!(
                     "This team (`{}`) cannot be pinged via this command; \
-                    it may need to be added to `triagebot.toml` on the master branch.",
+                    it may need to be added to `triagebot.toml` on the default branch.",
                     team_name.team,
                 ),
             );
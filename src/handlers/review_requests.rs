@@ -0,0 +1,204 @@
+//! Purpose: React to pull requests becoming ready for review and to review requests.
+//!
+//! When a draft pull request is marked as ready for review, the configured label
+//! (e.g. `S-waiting-on-review`) is applied. When a review is requested from a user or
+//! a team, a notification is recorded for the reviewers, as if they had been pinged.
+
+use crate::db::notifications;
+use crate::{
+    config::ReviewRequestsConfig,
+    github::{self, IssuesAction, IssuesEvent, Label},
+    handlers::Context,
+};
+use anyhow::Context as _;
+use std::convert::TryFrom;
+use tracing as log;
+
+pub(super) enum ReviewRequestsInput {
+    ReadyForReview { label: String },
+    ReviewerRequested(github::User),
+    TeamRequested(github::RequestedTeam),
+}
+
+pub(super) async fn parse_input(
+    _ctx: &Context,
+    event: &IssuesEvent,
+    config: Option<&ReviewRequestsConfig>,
+) -> Result<Option<ReviewRequestsInput>, String> {
+    Ok(config.and_then(|config| input(event, config)))
+}
+
+fn input(event: &IssuesEvent, config: &ReviewRequestsConfig) -> Option<ReviewRequestsInput> {
+    match event.action {
+        IssuesAction::ReadyForReview => config
+            .ready_label
+            .clone()
+            .map(|label| ReviewRequestsInput::ReadyForReview { label }),
+        IssuesAction::ReviewRequested if config.notify_reviewers => {
+            match (&event.requested_reviewer, &event.requested_team) {
+                (Some(user), _) => Some(ReviewRequestsInput::ReviewerRequested(user.clone())),
+                (None, Some(team)) => Some(ReviewRequestsInput::TeamRequested(team.clone())),
+                (None, None) => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub(super) async fn handle_input(
+    ctx: &Context,
+    _config: &ReviewRequestsConfig,
+    event: &IssuesEvent,
+    input: ReviewRequestsInput,
+) -> anyhow::Result<()> {
+    let (users, team_name) = match input {
+        ReviewRequestsInput::ReadyForReview { label } => {
+            return event
+                .issue
+                .add_labels(&ctx.github, vec![Label { name: label }])
+                .await;
+        }
+        ReviewRequestsInput::ReviewerRequested(user) => (vec![user], None),
+        ReviewRequestsInput::TeamRequested(team) => {
+            let team = match github::get_team(&ctx.github, &team.slug).await? {
+                Some(team) => team,
+                None => {
+                    log::debug!("requested team {} is not in the team data", team.slug);
+                    return Ok(());
+                }
+            };
+            let members = team
+                .members
+                .into_iter()
+                .map(|member| {
+                    let id = i64::try_from(member.github_id)
+                        .with_context(|| format!("user id {} out of bounds", member.github_id))?;
+                    Ok(github::User {
+                        id: Some(id),
+                        login: member.github,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            (members, Some(team.name))
+        }
+    };
+
+    let client = ctx.db.get().await;
+    for user in users {
+        let id = match user.id {
+            Some(id) => id,
+            None => match user.get_id(&ctx.github).await? {
+                Some(id) => id as i64,
+                None => continue,
+            },
+        };
+        notifications::record_username(&client, id, user.login)
+            .await
+            .context("failed to record username")?;
+        notifications::record_ping(
+            &client,
+            &notifications::Notification {
+                user_id: id,
+                origin_url: event.issue.html_url.clone(),
+                origin_html: event.issue.body.clone(),
+                time: chrono::Utc::now().into(),
+                short_description: Some(format!("Review requested: {}", event.issue.title)),
+                team_name: team_name.clone(),
+            },
+        )
+        .await
+        .context("failed to record ping")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(action: &str, extra: serde_json::Value) -> IssuesEvent {
+        let mut event = serde_json::json!({
+            "action": action,
+            "pull_request": {
+                "number": 1,
+                "body": "",
+                "created_at": "2021-01-01T00:00:00Z",
+                "updated_at": "2021-01-01T00:00:00Z",
+                "title": "Test",
+                "html_url": "https://github.com/o/r/pull/1",
+                "user": {"login": "author", "id": 1},
+                "labels": [],
+                "assignees": [],
+                "comments_url": "https://api.github.com/repos/o/r/issues/1/comments"
+            },
+            "repository": {"full_name": "o/r"}
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            event[key] = value.clone();
+        }
+        serde_json::from_value(event).unwrap()
+    }
+
+    fn config(ready_label: Option<&str>, notify_reviewers: bool) -> ReviewRequestsConfig {
+        ReviewRequestsConfig {
+            ready_label: ready_label.map(str::to_owned),
+            notify_reviewers,
+        }
+    }
+
+    #[test]
+    fn ready_for_review() {
+        let ready = event("ready_for_review", serde_json::json!({}));
+        assert!(matches!(
+            input(&ready, &config(Some("S-waiting-on-review"), false)),
+            Some(ReviewRequestsInput::ReadyForReview { label }) if label == "S-waiting-on-review"
+        ));
+        assert!(input(&ready, &config(None, true)).is_none());
+        let opened = event("opened", serde_json::json!({}));
+        assert!(input(&opened, &config(Some("S-waiting-on-review"), true)).is_none());
+    }
+
+    #[test]
+    fn requested_reviewer() {
+        let alice = serde_json::json!({"login": "alice", "id": 2});
+        let team = serde_json::json!({"name": "Compiler", "slug": "compiler"});
+        let user_requested = event(
+            "review_requested",
+            serde_json::json!({"requested_reviewer": alice.clone()}),
+        );
+        assert!(matches!(
+            input(&user_requested, &config(None, true)),
+            Some(ReviewRequestsInput::ReviewerRequested(user)) if user.login == "alice"
+        ));
+        // Review requests are only handled if enabled.
+        assert!(input(&user_requested, &config(Some("S-waiting-on-review"), false)).is_none());
+
+        let team_requested = event(
+            "review_requested",
+            serde_json::json!({"requested_team": team.clone()}),
+        );
+        assert!(matches!(
+            input(&team_requested, &config(None, true)),
+            Some(ReviewRequestsInput::TeamRequested(team)) if team.slug == "compiler"
+        ));
+
+        // GitHub doesn't send both, but the user would be the more specific one.
+        let both = event(
+            "review_requested",
+            serde_json::json!({"requested_reviewer": alice.clone(), "requested_team": team}),
+        );
+        assert!(matches!(
+            input(&both, &config(None, true)),
+            Some(ReviewRequestsInput::ReviewerRequested(user)) if user.login == "alice"
+        ));
+
+        let nobody = event("review_requested", serde_json::json!({}));
+        assert!(input(&nobody, &config(None, true)).is_none());
+        let removed = event(
+            "review_request_removed",
+            serde_json::json!({"requested_reviewer": alice}),
+        );
+        assert!(input(&removed, &config(None, true)).is_none());
+    }
+}
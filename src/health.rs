@@ -0,0 +1,139 @@
+//! Readiness checks, served at `/ready`.
+//!
+//! Each dependency of the bot is checked separately, so that the report shows which
+//! one is broken.
+
+use crate::handlers::Context;
+use anyhow::Context as _;
+use hyper::StatusCode;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// How long a single check may take before it is considered failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, serde::Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub database: Check,
+    pub github: Check,
+    pub team_data: Check,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Check {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl Readiness {
+    fn new(database: Check, github: Check, team_data: Check) -> Readiness {
+        Readiness {
+            ready: database.ok && github.ok && team_data.ok,
+            database,
+            github,
+            team_data,
+        }
+    }
+
+    /// The status of the `/ready` response: 503 if any check failed.
+    pub fn status(&self) -> StatusCode {
+        if self.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+pub async fn readiness(ctx: &Context) -> Readiness {
+    let (database, github, team_data) = futures::future::join3(
+        check(CHECK_TIMEOUT, check_database(ctx)),
+        check(CHECK_TIMEOUT, ctx.github.check_auth()),
+        check(CHECK_TIMEOUT, check_team_data(ctx)),
+    )
+    .await;
+    Readiness::new(database, github, team_data)
+}
+
+async fn check_database(ctx: &Context) -> anyhow::Result<()> {
+    let db = ctx.db.try_get().await?;
+    db.simple_query("SELECT 1")
+        .await
+        .context("failed to query the database")?;
+    Ok(())
+}
+
+async fn check_team_data(ctx: &Context) -> anyhow::Result<()> {
+    crate::team_data::teams(&ctx.github).await?;
+    Ok(())
+}
+
+async fn check(timeout: Duration, f: impl Future<Output = anyhow::Result<()>>) -> Check {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(timeout, f).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("timed out after {:?}", timeout)),
+    };
+    Check {
+        ok: result.is_ok(),
+        error: result.err().map(|e| format!("{:?}", e)),
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok() -> Check {
+        Check {
+            ok: true,
+            error: None,
+            duration_ms: 1,
+        }
+    }
+
+    #[test]
+    fn status() {
+        let report = Readiness::new(ok(), ok(), ok());
+        assert!(report.ready);
+        assert_eq!(report.status(), StatusCode::OK);
+
+        let failed = Check {
+            ok: false,
+            error: Some(String::from("connection refused")),
+            duration_ms: 1,
+        };
+        let report = Readiness::new(ok(), failed, ok());
+        assert!(!report.ready);
+        assert_eq!(report.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "ready": false,
+                "database": {"ok": true, "duration_ms": 1},
+                "github": {"ok": false, "error": "connection refused", "duration_ms": 1},
+                "team_data": {"ok": true, "duration_ms": 1},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn checks() {
+        let timeout = Duration::from_millis(50);
+        let passed = check(timeout, async { Ok(()) }).await;
+        assert!(passed.ok);
+        assert_eq!(passed.error, None);
+
+        let failed = check(timeout, async { Err(anyhow::anyhow!("no database")) }).await;
+        assert!(!failed.ok);
+        assert!(failed.error.unwrap().starts_with("no database"));
+
+        let stuck = check(timeout, futures::future::pending()).await;
+        assert!(!stuck.ok);
+        assert!(stuck.error.unwrap().starts_with("timed out after 50ms"));
+    }
+}
@@ -13,16 +13,20 @@ pub mod actions;
 pub mod agenda;
 mod changelogs;
 pub mod config;
+pub mod config_listing;
 pub mod db;
 pub mod github;
 pub mod handlers;
+pub mod health;
 pub mod interactions;
+pub mod metrics;
 pub mod notification_listing;
 pub mod payload;
 pub mod rfcbot;
 pub mod team;
 mod team_data;
 pub mod triage;
+pub mod webhook_queue;
 pub mod zulip;
 
 #[derive(Debug)]
@@ -34,6 +38,9 @@ pub enum EventName {
     Issue,
     Push,
     Create,
+    CheckRun,
+    CheckSuite,
+    WorkflowRun,
     Other,
 }
 
@@ -48,6 +55,9 @@ impl std::str::FromStr for EventName {
             "issues" => EventName::Issue,
             "push" => EventName::Push,
             "create" => EventName::Create,
+            "check_run" => EventName::CheckRun,
+            "check_suite" => EventName::CheckSuite,
+            "workflow_run" => EventName::WorkflowRun,
             _ => EventName::Other,
         })
     }
@@ -66,6 +76,9 @@ impl fmt::Display for EventName {
                 EventName::PullRequest => "pull_request",
                 EventName::Push => "push",
                 EventName::Create => "create",
+                EventName::CheckRun => "check_run",
+                EventName::CheckSuite => "check_suite",
+                EventName::WorkflowRun => "workflow_run",
                 EventName::Other => "other",
             }
         )
@@ -73,14 +86,53 @@ impl fmt::Display for EventName {
 }
 
 #[derive(Debug)]
-pub struct WebhookError(anyhow::Error);
+pub struct WebhookError {
+    error: anyhow::Error,
+    retryable: bool,
+}
+
+impl WebhookError {
+    /// Whether handling the event again may succeed, see `is_transient`.
+    pub fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+}
 
 impl From<anyhow::Error> for WebhookError {
     fn from(e: anyhow::Error) -> WebhookError {
-        WebhookError(e)
+        WebhookError {
+            retryable: is_transient(&e),
+            error: e,
+        }
     }
 }
 
+/// Whether `err` was caused by a temporary failure of GitHub or the database, rather
+/// than by something which would fail the same way the next time.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if cause.is::<github::RateLimited>() {
+            return true;
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return match err.status() {
+                Some(status) => {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                None => err.is_timeout() || err.is_connect() || err.is_request(),
+            };
+        }
+        cause.is::<tokio_postgres::Error>() || cause.is::<std::io::Error>()
+    })
+}
+
+/// What handling a webhook would have done, see `github::dry_run`.
+#[derive(Debug, serde::Serialize)]
+pub struct DryRunReport {
+    pub outcome: String,
+    pub planned_actions: Vec<github::PlannedAction>,
+}
+
 pub fn deserialize_payload<T: serde::de::DeserializeOwned>(v: &str) -> anyhow::Result<T> {
     let mut deserializer = serde_json::Deserializer::from_str(&v);
     let res: Result<T, _> = serde_path_to_error::deserialize(&mut deserializer);
@@ -93,16 +145,13 @@ pub fn deserialize_payload<T: serde::de::DeserializeOwned>(v: &str) -> anyhow::R
     }
 }
 
-pub async fn webhook(
-    event: EventName,
-    payload: String,
-    ctx: &handlers::Context,
-) -> Result<bool, WebhookError> {
+/// Deserializes a webhook payload into an event, returning `None` for events that
+/// need not be handled.
+pub fn parse_event(event: EventName, payload: &str) -> anyhow::Result<Option<github::Event>> {
     let event = match event {
         EventName::PullRequestReview => {
             let payload = deserialize_payload::<github::PullRequestReviewEvent>(&payload)
-                .context("PullRequestReview failed to deserialize")
-                .map_err(anyhow::Error::from)?;
+                .context("PullRequestReview failed to deserialize")?;
 
             log::info!("handling pull request review comment {:?}", payload);
 
@@ -126,8 +175,7 @@ pub async fn webhook(
         }
         EventName::PullRequestReviewComment => {
             let payload = deserialize_payload::<github::PullRequestReviewComment>(&payload)
-                .context("PullRequestReview(Comment) failed to deserialize")
-                .map_err(anyhow::Error::from)?;
+                .context("PullRequestReview(Comment) failed to deserialize")?;
 
             log::info!("handling pull request review comment {:?}", payload);
 
@@ -143,8 +191,7 @@ pub async fn webhook(
         }
         EventName::IssueComment => {
             let payload = deserialize_payload::<github::IssueCommentEvent>(&payload)
-                .context("IssueCommentEvent failed to deserialize")
-                .map_err(anyhow::Error::from)?;
+                .context("IssueCommentEvent failed to deserialize")?;
 
             log::info!("handling issue comment {:?}", payload);
 
@@ -152,8 +199,7 @@ pub async fn webhook(
         }
         EventName::Issue | EventName::PullRequest => {
             let payload = deserialize_payload::<github::IssuesEvent>(&payload)
-                .context(format!("{:?} failed to deserialize", event))
-                .map_err(anyhow::Error::from)?;
+                .context(format!("{:?} failed to deserialize", event))?;
 
             log::info!("handling issue event {:?}", payload);
 
@@ -161,8 +207,7 @@ pub async fn webhook(
         }
         EventName::Push => {
             let payload = deserialize_payload::<github::PushEvent>(&payload)
-                .with_context(|| format!("{:?} failed to deserialize", event))
-                .map_err(anyhow::Error::from)?;
+                .with_context(|| format!("{:?} failed to deserialize", event))?;
 
             log::info!("handling push event {:?}", payload);
 
@@ -170,33 +215,100 @@ pub async fn webhook(
         }
         EventName::Create => {
             let payload = deserialize_payload::<github::CreateEvent>(&payload)
-                .with_context(|| format!("{:?} failed to deserialize", event))
-                .map_err(anyhow::Error::from)?;
+                .with_context(|| format!("{:?} failed to deserialize", event))?;
 
             log::info!("handling create event {:?}", payload);
 
             github::Event::Create(payload)
         }
+        EventName::CheckRun => {
+            let payload = deserialize_payload::<github::CheckRunEvent>(&payload)
+                .with_context(|| format!("{:?} failed to deserialize", event))?;
+
+            log::info!("handling check run event {:?}", payload);
+
+            github::Event::CheckRun(payload)
+        }
+        EventName::CheckSuite => {
+            let payload = deserialize_payload::<github::CheckSuiteEvent>(&payload)
+                .with_context(|| format!("{:?} failed to deserialize", event))?;
+
+            log::info!("handling check suite event {:?}", payload);
+
+            github::Event::CheckSuite(payload)
+        }
+        EventName::WorkflowRun => {
+            let payload = deserialize_payload::<github::WorkflowRunEvent>(&payload)
+                .with_context(|| format!("{:?} failed to deserialize", event))?;
+
+            log::info!("handling workflow run event {:?}", payload);
+
+            github::Event::WorkflowRun(payload)
+        }
         // Other events need not be handled
         EventName::Other => {
-            return Ok(false);
+            return Ok(None);
         }
     };
-    let errors = handlers::handle(&ctx, &event).await;
-    let mut other_error = false;
+    Ok(Some(event))
+}
+
+/// Handles a webhook event.
+///
+/// The keys of the handlers which are done with the event are added to `completed`,
+/// and handlers whose key is already there are skipped, so that a failed event can be
+/// retried without repeating what succeeded. Handlers are done once they succeeded,
+/// or failed in a way which retrying would not fix; the event then fails without
+/// being retryable.
+pub async fn webhook(
+    event: EventName,
+    payload: String,
+    ctx: &handlers::Context,
+    completed: &mut Vec<String>,
+) -> Result<bool, WebhookError> {
+    let event = match parse_event(event, &payload)? {
+        Some(event) => event,
+        None => return Ok(false),
+    };
+    let ctx = ctx.for_repo(&event.repo_name()).await?;
+    let runs = handlers::handle(&ctx, &event, completed).await;
+    let mut unfinished = Vec::new();
+    let mut failed = Vec::new();
+    let mut commented = Vec::new();
     let mut message = String::new();
-    for err in errors {
-        match err {
-            HandlerError::Message(msg) => {
+    for run in runs {
+        let mut messages = Vec::new();
+        let mut transient = false;
+        let mut permanent = false;
+        for err in run.errors {
+            match err {
+                HandlerError::Message(msg) => messages.push(msg),
+                HandlerError::Other(err) => {
+                    log::error!("handling event with {} failed: {:?}", run.key, err);
+                    if is_transient(&err) {
+                        transient = true;
+                    } else {
+                        permanent = true;
+                    }
+                }
+            }
+        }
+        if permanent && !transient {
+            failed.push(run.key.clone());
+        }
+        if transient {
+            // The messages will be reported by the next attempt.
+            unfinished.push(run.key);
+        } else if messages.is_empty() {
+            completed.push(run.key);
+        } else {
+            for msg in messages {
                 if !message.is_empty() {
                     message.push_str("\n\n");
                 }
                 message.push_str(&msg);
             }
-            HandlerError::Other(err) => {
-                log::error!("handling event failed: {:?}", err);
-                other_error = true;
-            }
+            commented.push(run.key);
         }
     }
     if !message.is_empty() {
@@ -205,11 +317,82 @@ pub async fn webhook(
             cmnt.post(&ctx.github).await?;
         }
     }
-    if other_error {
-        Err(WebhookError(anyhow::anyhow!(
-            "handling failed, error logged",
-        )))
+    completed.extend(commented);
+    if !unfinished.is_empty() {
+        Err(WebhookError {
+            error: anyhow::anyhow!("{} failed, error logged", unfinished.join(", ")),
+            retryable: true,
+        })
+    } else if !failed.is_empty() {
+        Err(WebhookError {
+            error: anyhow::anyhow!("{} failed, error logged", failed.join(", ")),
+            retryable: false,
+        })
     } else {
         Ok(true)
     }
 }
+
+/// Handles the webhook without making any change on GitHub, reporting the writes
+/// which would have been made instead.
+pub async fn webhook_dry_run(
+    event: EventName,
+    payload: String,
+    ctx: &handlers::Context,
+) -> DryRunReport {
+    let (result, planned_actions) =
+        github::dry_run(webhook(event, payload, ctx, &mut Vec::new())).await;
+    let outcome = match result {
+        Ok(true) => String::from("processed"),
+        Ok(false) => String::from("ignored"),
+        Err(err) => format!("failed: {:?}", err),
+    };
+    DryRunReport {
+        outcome,
+        planned_actions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_error(status: u16) -> anyhow::Error {
+        status_error_with(status, &[])
+    }
+
+    fn status_error_with(status: u16, headers: &[(&str, &str)]) -> anyhow::Error {
+        let mut resp = hyper::Response::builder().status(status);
+        for (name, value) in headers {
+            resp = resp.header(*name, *value);
+        }
+        let resp = reqwest::Response::from(resp.body("").unwrap());
+        github::check_status(&resp).unwrap_err()
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(is_transient(&status_error(502)));
+        assert!(is_transient(&status_error(429)));
+        assert!(is_transient(&status_error(503).context("posting comment")));
+        assert!(!is_transient(&status_error(404)));
+        assert!(!is_transient(&status_error(422)));
+        // Missing permissions, unlike rate limits.
+        assert!(!is_transient(&status_error(403)));
+        assert!(is_transient(&status_error_with(
+            403,
+            &[("X-RateLimit-Remaining", "0")]
+        )));
+        assert!(is_transient(&status_error_with(
+            403,
+            &[("Retry-After", "60")]
+        )));
+        assert!(!is_transient(&status_error_with(
+            403,
+            &[("X-RateLimit-Remaining", "4000")]
+        )));
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(is_transient(&anyhow::Error::new(io)));
+        assert!(!is_transient(&anyhow::anyhow!("no such label")));
+    }
+}
@@ -6,16 +6,21 @@ use futures::StreamExt;
 use hyper::{header, Body, Request, Response, Server, StatusCode};
 use reqwest::Client;
 use route_recognizer::Router;
-use std::{env, net::SocketAddr, sync::Arc};
+use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::watch;
 use tracing as log;
 use tracing::Instrument;
-use triagebot::{db, github, handlers::Context, notification_listing, payload, EventName};
+use triagebot::{
+    db, github, handlers, handlers::Context, notification_listing, payload, webhook_queue,
+    EventName,
+};
 
 async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Body>, hyper::Error> {
     log::info!("request = {:?}", req);
     let mut router = Router::new();
     router.add("/triage", "index".to_string());
     router.add("/triage/:owner/:repo", "pulls".to_string());
+    router.add("/config/:owner/:repo", "config".to_string());
     let (req, body_stream) = req.into_parts();
 
     if let Ok(matcher) = router.recognize(req.uri.path()) {
@@ -24,6 +29,18 @@ async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Bod
             let owner = params.find("owner");
             let repo = params.find("repo");
             return triagebot::triage::pulls(ctx, owner.unwrap(), repo.unwrap()).await;
+        } else if matcher.handler().as_str() == "config" {
+            let params = matcher.params();
+            let repo = format!(
+                "{}/{}",
+                params.find("owner").unwrap(),
+                params.find("repo").unwrap()
+            );
+            return Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/html")
+                .body(Body::from(triagebot::config_listing::render(&ctx, &repo)))
+                .unwrap());
         } else {
             return triagebot::triage::index();
         }
@@ -34,6 +51,35 @@ async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Bod
             .body(Body::from("Triagebot is awaiting triage."))
             .unwrap());
     }
+    // Liveness: the server is up and answering requests.
+    if req.uri.path() == "/health" {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::from("OK"))
+            .unwrap());
+    }
+    // Readiness: the database, GitHub and the team API can be reached.
+    if req.uri.path() == "/ready" {
+        let report = triagebot::health::readiness(&ctx).await;
+        return Ok(Response::builder()
+            .status(report.status())
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&report).unwrap()))
+            .unwrap());
+    }
+    if req.uri.path() == "/metrics" {
+        return Ok(match triagebot::metrics::render() {
+            Ok(metrics) => Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/plain; version=0.0.4")
+                .body(Body::from(metrics))
+                .unwrap(),
+            Err(e) => Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(format!("{:?}", e)))
+                .unwrap(),
+        });
+    }
     if req.uri.path() == "/bors-commit-list" {
         let res = db::rustc_commits::get_commits_with_artifacts(&*ctx.db.get().await).await;
         let res = match res {
@@ -97,6 +143,35 @@ async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Bod
             .body(Body::from(triagebot::zulip::respond(&ctx, req).await))
             .unwrap());
     }
+    if let Some(delivery_id) = req.uri.path().strip_prefix("/admin/replay/") {
+        if req.method != hyper::Method::POST {
+            return Ok(Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .header(header::ALLOW, "POST")
+                .body(Body::empty())
+                .unwrap());
+        }
+        if !is_admin(&req.headers) {
+            return Ok(Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::from("Invalid authorization."))
+                .unwrap());
+        }
+        let dry_run = req.uri.query().map_or(false, |query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .any(|(k, v)| k == "dry_run" && v == "true")
+        });
+        return match webhook_queue::replay(&ctx, delivery_id, dry_run).await {
+            Ok(report) => Ok(Response::new(Body::from(report))),
+            Err(err) => {
+                log::error!("replay failed: {:?}", err);
+                Ok(Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::from(format!("replay failed: {:?}", err)))
+                    .unwrap())
+            }
+        };
+    }
     if req.uri.path() != "/github-hook" {
         return Ok(Response::builder()
             .status(StatusCode::NOT_FOUND)
@@ -131,20 +206,37 @@ async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Bod
             .unwrap());
     };
     log::debug!("event={}", event);
-    let signature = if let Some(sig) = req.headers.get("X-Hub-Signature") {
+    let delivery_id = match req.headers.get("X-GitHub-Delivery").map(|v| v.to_str()) {
+        Some(Ok(v)) => Some(v.to_owned()),
+        Some(Err(_)) => {
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from("X-GitHub-Delivery header must be UTF-8 encoded"))
+                .unwrap());
+        }
+        None => None,
+    };
+    log::debug!("delivery={:?}", delivery_id);
+    // Prefer the HMAC-SHA256 signature, falling back to the legacy SHA-1 one.
+    let signature_header = ["X-Hub-Signature-256", "X-Hub-Signature"]
+        .iter()
+        .find_map(|name| Some((*name, req.headers.get(*name)?)));
+    let signature = if let Some((name, sig)) = signature_header {
         match sig.to_str().ok() {
             Some(v) => v,
             None => {
                 return Ok(Response::builder()
                     .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from("X-Hub-Signature header must be UTF-8 encoded"))
+                    .body(Body::from(format!("{} header must be UTF-8 encoded", name)))
                     .unwrap());
             }
         }
     } else {
         return Ok(Response::builder()
             .status(StatusCode::BAD_REQUEST)
-            .body(Body::from("X-Hub-Signature header must be set"))
+            .body(Body::from(
+                "X-Hub-Signature-256 or X-Hub-Signature header must be set",
+            ))
             .unwrap());
     };
     log::debug!("signature={}", signature);
@@ -171,10 +263,34 @@ async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Bod
                 .unwrap());
         }
     };
+    triagebot::metrics::record_webhook_event(&event.to_string());
 
-    match triagebot::webhook(event, payload, &ctx).await {
-        Ok(true) => Ok(Response::new(Body::from("processed request"))),
-        Ok(false) => Ok(Response::new(Body::from("ignored request"))),
+    // In dry-run mode, handle the event right away to report what would have been done.
+    if ctx.github.is_dry_run() {
+        let report = triagebot::webhook_dry_run(event, payload, &ctx).await;
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&report).unwrap()))
+            .unwrap());
+    }
+
+    // Other events need not be handled, so don't bother queueing them.
+    if let EventName::Other = event {
+        return Ok(Response::new(Body::from("ignored request")));
+    }
+
+    // Deliveries which were already received are skipped. They can be handled again
+    // through `/admin/replay/`, which unlike this route is authenticated.
+    match webhook_queue::enqueue(&ctx, &event, &payload, delivery_id.as_deref()).await {
+        Ok(Some(id)) => {
+            log::debug!("queued webhook event {}", id);
+            Ok(Response::new(Body::from("queued request")))
+        }
+        Ok(None) => {
+            log::info!("skipping already received delivery {:?}", delivery_id);
+            Ok(Response::new(Body::from("duplicate request")))
+        }
         Err(err) => {
             log::error!("request failed: {:?}", err);
             Ok(Response::builder()
@@ -185,7 +301,63 @@ async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Bod
     }
 }
 
-async fn run_server(addr: SocketAddr) -> anyhow::Result<()> {
+/// Checks the `Authorization: Bearer <token>` header against `TRIAGEBOT_ADMIN_TOKEN`.
+///
+/// Admin routes are disabled if that variable is not set.
+fn is_admin(headers: &header::HeaderMap) -> bool {
+    let expected = env::var("TRIAGEBOT_ADMIN_TOKEN").unwrap_or_default();
+    has_token(headers, &expected)
+}
+
+fn has_token(headers: &header::HeaderMap, expected: &str) -> bool {
+    if expected.is_empty() {
+        return false;
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match token {
+        Some(token) => {
+            token.len() == expected.len()
+                && openssl::memcmp::eq(token.as_bytes(), expected.as_bytes())
+        }
+        None => false,
+    }
+}
+
+/// Time given to queue workers to release the events they were handling on shutdown.
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolves once the process receives SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                log::error!("failed to listen for SIGTERM: {:?}", e);
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
+
+async fn run_server(
+    addr: SocketAddr,
+    queue_workers: usize,
+    shutdown_timeout: Duration,
+) -> anyhow::Result<()> {
     log::info!("Listening on http://{}", addr);
 
     let pool = db::ClientPool::new();
@@ -195,17 +367,26 @@ async fn run_server(addr: SocketAddr) -> anyhow::Result<()> {
 
     let client = Client::new();
     let gh = github::GithubClient::new_with_default_token(client.clone());
-    let oc = octocrab::OctocrabBuilder::new()
-        .personal_token(github::default_token_from_env())
-        .build()
-        .expect("Failed to build octograb.");
+    let oc = gh.octocrab().expect("Failed to build octograb.");
     let ctx = Arc::new(Context {
-        username: String::from("rustbot"),
+        username: handlers::bot_username(),
         db: pool,
         github: gh,
         octocrab: oc,
     });
 
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let workers = (0..queue_workers)
+        .map(|_| {
+            tokio::spawn(webhook_queue::run_worker(
+                ctx.clone(),
+                shutdown_rx.clone(),
+                shutdown_timeout,
+            ))
+        })
+        .collect::<Vec<_>>();
+    tokio::spawn(webhook_queue::run_cleanup(ctx.clone(), shutdown_rx.clone()));
+
     let svc = hyper::service::make_service_fn(move |_conn| {
         let ctx = ctx.clone();
         async move {
@@ -225,9 +406,42 @@ async fn run_server(addr: SocketAddr) -> anyhow::Result<()> {
             }))
         }
     });
-    let serve_future = Server::bind(&addr).serve(svc);
+    let mut server_shutdown = shutdown_rx.clone();
+    let serve_future = Server::bind(&addr)
+        .serve(svc)
+        .with_graceful_shutdown(async move {
+            let _ = server_shutdown.changed().await;
+        });
+    tokio::pin!(serve_future);
 
-    serve_future.await?;
+    tokio::select! {
+        res = &mut serve_future => return Ok(res?),
+        _ = shutdown_signal() => {}
+    }
+
+    // Stop accepting connections and claiming queued events, and give the requests
+    // and events being handled some time to finish. Workers stop handling their event
+    // after `shutdown_timeout` themselves, but need a bit more time to put it back in
+    // the queue.
+    log::info!(
+        "shutting down, waiting up to {:?} for in-flight work",
+        shutdown_timeout
+    );
+    let _ = shutdown_tx.send(true);
+    let drain = async {
+        serve_future.await?;
+        for worker in workers {
+            worker.await?;
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    match tokio::time::timeout(shutdown_timeout + RELEASE_TIMEOUT, drain).await {
+        Ok(res) => res?,
+        Err(_) => log::warn!(
+            "in-flight work did not finish within {:?}, exiting anyway",
+            shutdown_timeout
+        ),
+    }
     Ok(())
 }
 
@@ -244,8 +458,64 @@ async fn main() {
         .ok()
         .map(|p| p.parse::<u16>().expect("parsed PORT"))
         .unwrap_or(8000);
+    let queue_workers = env::var("WEBHOOK_QUEUE_WORKERS")
+        .ok()
+        .map(|p| p.parse::<usize>().expect("parsed WEBHOOK_QUEUE_WORKERS"))
+        .unwrap_or(4);
+    let shutdown_timeout = env::var("SHUTDOWN_TIMEOUT_SECS")
+        .ok()
+        .map(|p| p.parse::<u64>().expect("parsed SHUTDOWN_TIMEOUT_SECS"))
+        .unwrap_or(30);
     let addr = ([0, 0, 0, 0], port).into();
-    if let Err(e) = run_server(addr).await {
+    if let Err(e) = run_server(addr, queue_workers, Duration::from_secs(shutdown_timeout)).await {
         eprintln!("Failed to run server: {:?}", e);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(authorization: Option<&str>) -> header::HeaderMap {
+        let mut headers = header::HeaderMap::new();
+        if let Some(value) = authorization {
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn admin_token() {
+        assert!(has_token(&headers(Some("Bearer s3cret")), "s3cret"));
+        assert!(!has_token(&headers(None), "s3cret"));
+        assert!(!has_token(&headers(Some("Bearer wrong!")), "s3cret"));
+        assert!(!has_token(&headers(Some("Bearer s3cret2")), "s3cret"));
+        assert!(!has_token(&headers(Some("Bearer ")), "s3cret"));
+        assert!(!has_token(&headers(Some("s3cret")), "s3cret"));
+        assert!(!has_token(&headers(Some("Basic s3cret")), "s3cret"));
+        // Without a configured token, admin routes are disabled.
+        assert!(!has_token(&headers(Some("Bearer ")), ""));
+        assert!(!has_token(&headers(None), ""));
+    }
+
+    #[tokio::test]
+    async fn replay_requires_admin_token() {
+        let github = github::GithubClient::new(Client::new(), String::from("token"));
+        let ctx = Arc::new(Context {
+            username: String::from("rustbot"),
+            db: db::ClientPool::new(),
+            octocrab: github.octocrab().unwrap(),
+            github,
+        });
+        for authorization in &[None, Some("Bearer wrong")] {
+            let mut req = Request::post("/admin/replay/1234");
+            if let Some(value) = authorization {
+                req = req.header(header::AUTHORIZATION, *value);
+            }
+            let resp = serve_req(req.body(Body::empty()).unwrap(), ctx.clone())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Prometheus metrics, served in the text exposition format at `/metrics`.
+
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter_vec, register_int_gauge_vec,
+    Encoder, Histogram, HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use std::time::Instant;
+
+lazy_static::lazy_static! {
+    static ref WEBHOOK_EVENTS: IntCounterVec = register_int_counter_vec!(
+        "triagebot_webhook_events_total",
+        "Webhook deliveries received, by event name",
+        &["event"]
+    )
+    .unwrap();
+    static ref HANDLER_RUNS: IntCounterVec = register_int_counter_vec!(
+        "triagebot_handler_runs_total",
+        "Handler runs, by handler and outcome (success, message or other)",
+        &["handler", "outcome"]
+    )
+    .unwrap();
+    static ref HANDLER_DURATION: HistogramVec = register_histogram_vec!(
+        "triagebot_handler_duration_seconds",
+        "Time spent in each handler",
+        &["handler"]
+    )
+    .unwrap();
+    static ref GITHUB_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "triagebot_github_requests_total",
+        "Requests sent to the GitHub API, by method and response status",
+        &["method", "status"]
+    )
+    .unwrap();
+    static ref GITHUB_RATE_LIMIT_REMAINING: IntGaugeVec = register_int_gauge_vec!(
+        "triagebot_github_rate_limit_remaining",
+        "Remaining GitHub API requests in the current rate limit window, by resource",
+        &["resource"]
+    )
+    .unwrap();
+    static ref DB_POOL_WAIT: Histogram = register_histogram!(
+        "triagebot_db_pool_wait_seconds",
+        "Time spent waiting for a database connection from the pool"
+    )
+    .unwrap();
+}
+
+pub fn record_webhook_event(event: &str) {
+    WEBHOOK_EVENTS.with_label_values(&[event]).inc();
+}
+
+/// Records a handler run which started at `started`. `outcome` is `success`, or the
+/// kind of `HandlerError` it reported (`message` or `other`).
+pub fn record_handler(handler: &str, started: Instant, outcome: &str) {
+    HANDLER_RUNS.with_label_values(&[handler, outcome]).inc();
+    HANDLER_DURATION
+        .with_label_values(&[handler])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+pub fn record_github_response(method: &reqwest::Method, resp: &reqwest::Response) {
+    GITHUB_REQUESTS
+        .with_label_values(&[method.as_str(), resp.status().as_str()])
+        .inc();
+
+    let header = |name: &str| resp.headers().get(name).and_then(|v| v.to_str().ok());
+    if let Some(remaining) = header("X-RateLimit-Remaining").and_then(|r| r.parse().ok()) {
+        let resource = header("X-RateLimit-Resource").unwrap_or("core");
+        GITHUB_RATE_LIMIT_REMAINING
+            .with_label_values(&[resource])
+            .set(remaining);
+    }
+}
+
+pub fn record_db_pool_wait(started: Instant) {
+    DB_POOL_WAIT.observe(started.elapsed().as_secs_f64());
+}
+
+/// Renders all metrics in the Prometheus text format.
+pub fn render() -> anyhow::Result<String> {
+    let mut buf = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_recorded_metrics() {
+        record_webhook_event("issue_comment");
+        record_handler("relabel", Instant::now(), "message");
+        let rendered = render().unwrap();
+        assert!(rendered.contains(r#"triagebot_webhook_events_total{event="issue_comment"}"#));
+        assert!(rendered
+            .contains(r#"triagebot_handler_runs_total{handler="relabel",outcome="message"} 1"#));
+        assert!(
+            rendered.contains(r#"triagebot_handler_duration_seconds_count{handler="relabel"} 1"#)
+        );
+    }
+}
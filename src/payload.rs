@@ -12,8 +12,42 @@ impl fmt::Display for SignedPayloadError {
 
 impl std::error::Error for SignedPayloadError {}
 
+/// Returns the configured webhook secrets.
+///
+/// `GITHUB_WEBHOOK_SECRET` may contain several comma-separated secrets, which
+/// allows rotating the secret without dropping deliveries signed with the old
+/// one during the switchover.
+fn webhook_secrets() -> Vec<String> {
+    std::env::var("GITHUB_WEBHOOK_SECRET")
+        .expect("Missing GITHUB_WEBHOOK_SECRET")
+        .split(',')
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Checks the signature of a webhook payload against the configured secrets.
+///
+/// `signature` is the value of either the `X-Hub-Signature-256` header
+/// (`sha256=<hex>`) or the legacy `X-Hub-Signature` header (`sha1=<hex>`).
 pub fn assert_signed(signature: &str, payload: &[u8]) -> Result<(), SignedPayloadError> {
-    let signature = signature.get("sha1=".len()..).ok_or(SignedPayloadError)?;
+    let secrets = webhook_secrets();
+    verify_signature(signature, payload, secrets.iter().map(|s| s.as_bytes()))
+}
+
+fn verify_signature<'a>(
+    signature: &str,
+    payload: &[u8],
+    secrets: impl IntoIterator<Item = &'a [u8]>,
+) -> Result<(), SignedPayloadError> {
+    let (digest, signature) = if let Some(sig) = signature.strip_prefix("sha256=") {
+        (MessageDigest::sha256(), sig)
+    } else if let Some(sig) = signature.strip_prefix("sha1=") {
+        (MessageDigest::sha1(), sig)
+    } else {
+        tracing::trace!("unknown signature algorithm in {:?}", signature);
+        return Err(SignedPayloadError);
+    };
     let signature = match hex::decode(&signature) {
         Ok(e) => e,
         Err(e) => {
@@ -22,18 +56,63 @@ pub fn assert_signed(signature: &str, payload: &[u8]) -> Result<(), SignedPayloa
         }
     };
 
-    let key = PKey::hmac(
-        std::env::var("GITHUB_WEBHOOK_SECRET")
-            .expect("Missing GITHUB_WEBHOOK_SECRET")
-            .as_bytes(),
-    )
-    .unwrap();
-    let mut signer = Signer::new(MessageDigest::sha1(), &key).unwrap();
-    signer.update(&payload).unwrap();
-    let hmac = signer.sign_to_vec().unwrap();
-
-    if !memcmp::eq(&hmac, &signature) {
-        return Err(SignedPayloadError);
+    for secret in secrets {
+        let key = PKey::hmac(secret).unwrap();
+        let mut signer = Signer::new(digest, &key).unwrap();
+        signer.update(&payload).unwrap();
+        let hmac = signer.sign_to_vec().unwrap();
+
+        if hmac.len() == signature.len() && memcmp::eq(&hmac, &signature) {
+            return Ok(());
+        }
+    }
+    Err(SignedPayloadError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"Hello, World!";
+
+    // Sample values from the GitHub documentation on validating webhook deliveries.
+    const SHA256: &str = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    fn sign_sha1(secret: &str) -> String {
+        let key = PKey::hmac(secret.as_bytes()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha1(), &key).unwrap();
+        signer.update(PAYLOAD).unwrap();
+        format!("sha1={}", hex::encode(signer.sign_to_vec().unwrap()))
+    }
+
+    #[test]
+    fn sha256() {
+        assert!(
+            verify_signature(SHA256, PAYLOAD, vec![&b"It's a Secret to Everybody"[..]]).is_ok()
+        );
+        assert!(verify_signature(SHA256, PAYLOAD, vec![&b"wrong"[..]]).is_err());
+    }
+
+    #[test]
+    fn sha1() {
+        let sig = sign_sha1("secret");
+        assert!(verify_signature(&sig, PAYLOAD, vec![&b"secret"[..]]).is_ok());
+        assert!(verify_signature(&sig, b"tampered", vec![&b"secret"[..]]).is_err());
+    }
+
+    #[test]
+    fn rotated_secrets() {
+        let sig = sign_sha1("old");
+        assert!(verify_signature(&sig, PAYLOAD, vec![&b"new"[..], &b"old"[..]]).is_ok());
+        assert!(verify_signature(&sig, PAYLOAD, vec![&b"new"[..]]).is_err());
+        assert!(verify_signature(SHA256, PAYLOAD, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        let secrets = vec![&b"It's a Secret to Everybody"[..]];
+        assert!(verify_signature("md5=abcd", PAYLOAD, secrets.clone()).is_err());
+        assert!(verify_signature("sha256=zz", PAYLOAD, secrets.clone()).is_err());
+        assert!(verify_signature("sha256=abcd", PAYLOAD, secrets).is_err());
     }
-    Ok(())
 }
@@ -0,0 +1,381 @@
+//! Durable processing of GitHub webhooks.
+//!
+//! Incoming deliveries are stored in the database and acknowledged right away. Background
+//! workers then run them through `crate::webhook`, retrying failures with exponential
+//! backoff. Only the handlers which failed are run again, and only if they failed in a
+//! way that retrying may fix. Events which still fail after `MAX_ATTEMPTS`, or can't be
+//! retried, are kept in a dead-letter state (`status = 'dead'` in the `webhook_events`
+//! table) for inspection.
+//!
+//! Deliveries are kept for `WEBHOOK_EVENT_RETENTION_DAYS` (7 by default), during which
+//! they can be replayed with `replay`. Older ones are deleted by `run_cleanup` every
+//! hour.
+//!
+//! On shutdown, workers don't claim new events, and get some time to finish the event
+//! they are handling. Past that, they stop handling it and release their lease, so that
+//! it is picked up again right away by the next process. An event interrupted anyway
+//! is picked up again once its lease expires.
+
+use crate::db::webhook_events::{self, QueuedEvent, StoredEvent};
+use crate::github::Repository;
+use crate::handlers::Context;
+use crate::EventName;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tracing as log;
+
+/// Number of attempts before an event is moved to the dead-letter state.
+const MAX_ATTEMPTS: i32 = 6;
+/// Delay before the first retry; doubled after every further failure.
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+/// How long a worker may hold an event before it is handed to another worker.
+const LEASE: Duration = Duration::from_secs(10 * 60);
+/// How often idle workers look for retries which became due.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// How often handled events past their retention are deleted.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Default number of days handled events are kept. GitHub allows redelivering events
+/// for three days, and duplicates are only detected while the original is stored.
+const DEFAULT_RETENTION_DAYS: u64 = 7;
+
+lazy_static::lazy_static! {
+    static ref NEW_EVENT: Notify = Notify::new();
+}
+
+fn retention() -> Duration {
+    let days = std::env::var("WEBHOOK_EVENT_RETENTION_DAYS")
+        .ok()
+        .and_then(|d| d.parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    Duration::from_secs(days * 24 * 60 * 60)
+}
+
+fn parse_event_name(name: &str) -> EventName {
+    match name.parse::<EventName>() {
+        Ok(v) => v,
+        Err(never) => match never {},
+    }
+}
+
+/// Stores the event for processing by a worker, returning its id in the queue.
+///
+/// Returns `None` if the delivery with the given `X-GitHub-Delivery` id was already
+/// received; use `replay` to intentionally handle it again.
+pub async fn enqueue(
+    ctx: &Context,
+    event: &EventName,
+    payload: &str,
+    delivery_id: Option<&str>,
+) -> anyhow::Result<Option<i64>> {
+    #[derive(serde::Deserialize)]
+    struct PayloadRepository {
+        repository: Option<Repository>,
+    }
+    let repo = serde_json::from_str::<PayloadRepository>(payload)
+        .ok()
+        .and_then(|p| p.repository)
+        .map(|r| r.full_name);
+
+    let id = webhook_events::enqueue(
+        &*ctx.db.get().await,
+        &event.to_string(),
+        payload,
+        delivery_id,
+        repo.as_deref(),
+    )
+    .await?;
+    if id.is_some() {
+        NEW_EVENT.notify_one();
+    }
+    Ok(id)
+}
+
+/// Processes queued events until `shutdown` is set to `true`, after which the event
+/// being handled is given `grace` to finish.
+pub async fn run_worker(ctx: Arc<Context>, mut shutdown: watch::Receiver<bool>, grace: Duration) {
+    while !*shutdown.borrow() {
+        match process_next(&ctx, interrupted(shutdown.clone(), grace)).await {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => log::error!("failed to process webhook queue: {:?}", e),
+        }
+        // Wait for a new delivery, but wake up regularly to pick up due retries.
+        tokio::select! {
+            _ = tokio::time::timeout(POLL_INTERVAL, NEW_EVENT.notified()) => {}
+            _ = shutdown.changed() => {}
+        }
+    }
+    log::debug!("webhook queue worker stopped");
+}
+
+/// Deletes the handled events received before the retention period every
+/// `CLEANUP_INTERVAL`, until `shutdown` is set to `true`.
+pub async fn run_cleanup(ctx: Arc<Context>, mut shutdown: watch::Receiver<bool>) {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+    while !*shutdown.borrow() {
+        tokio::select! {
+            _ = interval.tick() => {}
+            changed = shutdown.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                continue;
+            }
+        }
+        match webhook_events::delete_finished(&*ctx.db.get().await, retention()).await {
+            Ok(deleted) => log::debug!("deleted {} finished webhook events", deleted),
+            Err(e) => log::error!("failed to delete finished webhook events: {:?}", e),
+        }
+    }
+}
+
+/// Resolves `grace` after `shutdown` is set to `true`.
+async fn interrupted(mut shutdown: watch::Receiver<bool>, grace: Duration) {
+    while !*shutdown.borrow() {
+        if shutdown.changed().await.is_err() {
+            // Shutdown can't be requested anymore.
+            return std::future::pending().await;
+        }
+    }
+    tokio::time::sleep(grace).await;
+}
+
+/// Handles a single event from the queue, returning whether there was one. Handling
+/// is abandoned if `interrupted` resolves first.
+async fn process_next(
+    ctx: &Context,
+    interrupted: impl Future<Output = ()>,
+) -> anyhow::Result<bool> {
+    let event = webhook_events::claim_next(&*ctx.db.get().await, LEASE).await?;
+    let QueuedEvent {
+        event_id,
+        event_name,
+        payload,
+        attempts,
+        received_at,
+        mut completed_handlers,
+    } = match event {
+        Some(event) => event,
+        None => return Ok(false),
+    };
+    log::info!(
+        "processing webhook event {} ({}, received at {}, attempt {})",
+        event_id,
+        event_name,
+        received_at,
+        attempts
+    );
+
+    let result = {
+        let handling = crate::webhook(
+            parse_event_name(&event_name),
+            payload,
+            ctx,
+            &mut completed_handlers,
+        );
+        tokio::select! {
+            result = handling => Some(result),
+            _ = interrupted => None,
+        }
+    };
+
+    let db = ctx.db.get().await;
+    match result {
+        None => {
+            log::warn!(
+                "stopped handling webhook event {} to shut down, releasing it",
+                event_id
+            );
+            webhook_events::release(&db, event_id).await?;
+        }
+        Some(Ok(_)) => webhook_events::mark_done(&db, event_id).await?,
+        Some(Err(err)) => {
+            let retry_in = if err.is_retryable() && attempts < MAX_ATTEMPTS {
+                Some(backoff(attempts))
+            } else {
+                None
+            };
+            log::error!(
+                "webhook event {} failed (attempt {}), retry in {:?}: {:?}",
+                event_id,
+                attempts,
+                retry_in,
+                err
+            );
+            webhook_events::mark_failed(
+                &db,
+                event_id,
+                &format!("{:?}", err),
+                &completed_handlers,
+                retry_in,
+            )
+            .await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Feeds a stored delivery through all the handlers again, bypassing the queue.
+///
+/// In dry-run mode nothing is changed on GitHub, and the returned report lists the
+/// writes which would have been made.
+pub async fn replay(ctx: &Context, delivery_id: &str, dry_run: bool) -> anyhow::Result<String> {
+    let stored = webhook_events::get_by_delivery(&*ctx.db.get().await, delivery_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("no stored delivery with id {}", delivery_id))?;
+    replay_stored(ctx, stored, dry_run).await
+}
+
+async fn replay_stored(
+    ctx: &Context,
+    stored: StoredEvent,
+    dry_run: bool,
+) -> anyhow::Result<String> {
+    log::info!(
+        "replaying delivery {:?} ({} on {:?}, received at {}, status {})",
+        stored.delivery_id,
+        stored.event_name,
+        stored.repo,
+        stored.received_at,
+        stored.status
+    );
+
+    let event_name = parse_event_name(&stored.event_name);
+    if dry_run {
+        let report = crate::webhook_dry_run(event_name, stored.payload, ctx).await;
+        return Ok(serde_json::to_string_pretty(&report)?);
+    }
+    match crate::webhook(event_name, stored.payload, ctx, &mut Vec::new()).await {
+        Ok(true) => Ok(String::from("processed request")),
+        Ok(false) => Ok(String::from("ignored request")),
+        Err(err) => Err(anyhow::anyhow!("replay failed: {:?}", err)),
+    }
+}
+
+fn backoff(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 10) as u32;
+    BASE_BACKOFF * 2u32.pow(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::github::{GithubClient, GithubUrls};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Response, Server, StatusCode};
+
+    /// A GitHub instance where `replay-test/repo` has an empty `triagebot.toml` and
+    /// everything else is missing.
+    fn mock_github() -> GithubClient {
+        let make_svc = make_service_fn(|_conn| async {
+            Ok::<_, hyper::Error>(service_fn(|req: hyper::Request<Body>| async move {
+                let resp = if req.uri().path() == "/raw/replay-test/repo/main/triagebot.toml" {
+                    Response::new(Body::empty())
+                } else {
+                    let mut resp = Response::new(Body::empty());
+                    *resp.status_mut() = StatusCode::NOT_FOUND;
+                    resp
+                };
+                Ok::<_, hyper::Error>(resp)
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        GithubClient::new(reqwest::Client::new(), String::from("token")).with_urls(GithubUrls {
+            api: url.clone(),
+            graphql: format!("{}/graphql", url),
+            raw: format!("{}/raw", url),
+        })
+    }
+
+    const COMMENT_EVENT: &str = r#"{
+        "action": "created",
+        "issue": {
+            "number": 1,
+            "body": "",
+            "created_at": "2021-01-01T00:00:00Z",
+            "updated_at": "2021-01-01T00:00:00Z",
+            "title": "Test",
+            "html_url": "https://github.com/replay-test/repo/issues/1",
+            "user": {"login": "someone", "id": 1},
+            "labels": [],
+            "assignees": [],
+            "comments_url": "https://api.github.com/repos/replay-test/repo/issues/1/comments"
+        },
+        "comment": {
+            "id": 2,
+            "body": "@rustbot label +bug",
+            "html_url": "https://github.com/replay-test/repo/issues/1#issuecomment-2",
+            "user": {"login": "someone", "id": 1},
+            "updated_at": "2021-01-01T00:00:00Z"
+        },
+        "repository": {"full_name": "replay-test/repo", "default_branch": "main"}
+    }"#;
+
+    #[tokio::test]
+    async fn replay_stored_event() {
+        let github = mock_github();
+        let ctx = Context {
+            username: String::from("rustbot"),
+            db: crate::db::ClientPool::new(),
+            octocrab: github.octocrab().unwrap(),
+            github,
+        };
+        let stored = StoredEvent {
+            event_id: 1,
+            event_name: String::from("issue_comment"),
+            delivery_id: Some(String::from("1234")),
+            repo: Some(String::from("replay-test/repo")),
+            payload: String::from(COMMENT_EVENT),
+            status: String::from("done"),
+            received_at: chrono::Utc::now().into(),
+        };
+        let report = replay_stored(&ctx, stored, true).await.unwrap();
+        let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(report["outcome"], "processed");
+        // The repository doesn't enable `[relabel]`, which is reported in a comment.
+        let actions = report["planned_actions"].as_array().unwrap();
+        assert_eq!(actions.len(), 1, "{:?}", actions);
+        assert_eq!(actions[0]["method"], "POST");
+        assert_eq!(
+            actions[0]["url"],
+            "https://api.github.com/repos/replay-test/repo/issues/1/comments"
+        );
+        let body = actions[0]["body"].as_str().unwrap();
+        assert!(
+            body.contains("The feature `relabel` is not enabled"),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn interrupted_after_grace() {
+        let grace = Duration::from_millis(50);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let interrupted = interrupted(shutdown_rx, grace);
+        tokio::pin!(interrupted);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), &mut interrupted)
+                .await
+                .is_err(),
+            "interrupted before shutdown"
+        );
+
+        shutdown_tx.send(true).unwrap();
+        let requested = std::time::Instant::now();
+        interrupted.await;
+        assert!(requested.elapsed() >= grace);
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        assert_eq!(backoff(1), Duration::from_secs(30));
+        assert_eq!(backoff(2), Duration::from_secs(60));
+        assert_eq!(backoff(3), Duration::from_secs(120));
+        assert_eq!(backoff(100), Duration::from_secs(30 * 1024));
+    }
+}
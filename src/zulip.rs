@@ -45,7 +45,43 @@ struct ResponseOwned {
     content: String,
 }
 
-pub const BOT_EMAIL: &str = "triage-rust-lang-bot@zulipchat.com";
+/// Email of the bot's Zulip account, from `ZULIP_BOT_EMAIL`.
+pub fn bot_email() -> String {
+    env::var("ZULIP_BOT_EMAIL").unwrap_or_else(|_| "triage-rust-lang-bot@zulipchat.com".to_owned())
+}
+
+/// Base URL of the Zulip realm, from `ZULIP_URL`, without a trailing slash.
+pub fn zulip_url() -> String {
+    env::var("ZULIP_URL")
+        .map(|url| url.trim_end_matches('/').to_owned())
+        .unwrap_or_else(|_| "https://rust-lang.zulipchat.com".to_owned())
+}
+
+/// Sends a write request to the Zulip API as the bot.
+///
+/// When `client` is in dry-run mode, the request is recorded as a planned action
+/// instead, like writes to GitHub, and answered as if it succeeded.
+async fn send_request(
+    client: &GithubClient,
+    req: reqwest::RequestBuilder,
+) -> anyhow::Result<reqwest::Response> {
+    if client.is_dry_run() {
+        let req = req.build().context("building Zulip request")?;
+        let body = req
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| String::from_utf8_lossy(b).into_owned());
+        client.record_planned(req.method().as_str(), req.url().as_str(), body);
+        return Ok(reqwest::Response::from(hyper::Response::new(
+            r#"{"result": "success", "msg": "", "id": 0}"#,
+        )));
+    }
+    let bot_api_token = env::var("ZULIP_API_TOKEN").expect("ZULIP_API_TOKEN");
+    Ok(req
+        .basic_auth(bot_email(), Some(&bot_api_token))
+        .send()
+        .await?)
+}
 
 pub async fn to_github_id(client: &GithubClient, zulip_id: usize) -> anyhow::Result<Option<i64>> {
     let map = crate::team_data::zulip_map(client).await?;
@@ -242,8 +278,8 @@ async fn execute_for_other_user(
     let members = ctx
         .github
         .raw()
-        .get("https://rust-lang.zulipchat.com/api/v1/users")
-        .basic_auth(BOT_EMAIL, Some(&bot_api_token))
+        .get(format!("{}/api/v1/users", zulip_url()))
+        .basic_auth(bot_email(), Some(&bot_api_token))
         .send()
         .await;
     let members = match members {
@@ -318,7 +354,7 @@ async fn execute_for_other_user(
         },
         content: &message,
     }
-    .send(ctx.github.raw())
+    .send(&ctx.github)
     .await;
 
     match res {
@@ -395,7 +431,7 @@ impl Recipient<'_> {
     }
 
     pub fn url(&self) -> String {
-        format!("https://rust-lang.zulipchat.com/#narrow/{}", self.narrow())
+        format!("{}/#narrow/{}", zulip_url(), self.narrow())
     }
 }
 
@@ -437,9 +473,7 @@ impl<'a> MessageApiRequest<'a> {
         self.recipient.url()
     }
 
-    pub async fn send(&self, client: &reqwest::Client) -> anyhow::Result<reqwest::Response> {
-        let bot_api_token = env::var("ZULIP_API_TOKEN").expect("ZULIP_API_TOKEN");
-
+    pub async fn send(&self, client: &GithubClient) -> anyhow::Result<reqwest::Response> {
         #[derive(serde::Serialize)]
         struct SerializedApi<'a> {
             #[serde(rename = "type")]
@@ -450,9 +484,9 @@ impl<'a> MessageApiRequest<'a> {
             content: &'a str,
         }
 
-        Ok(client
-            .post("https://rust-lang.zulipchat.com/api/v1/messages")
-            .basic_auth(BOT_EMAIL, Some(&bot_api_token))
+        let req = client
+            .raw()
+            .post(format!("{}/api/v1/messages", zulip_url()))
             .form(&SerializedApi {
                 type_: match self.recipient {
                     Recipient::Stream { .. } => "stream",
@@ -467,9 +501,8 @@ impl<'a> MessageApiRequest<'a> {
                     Recipient::Private { .. } => None,
                 },
                 content: self.content,
-            })
-            .send()
-            .await?)
+            });
+        send_request(client, req).await
     }
 }
 
@@ -488,9 +521,7 @@ pub struct UpdateMessageApiRequest<'a> {
 }
 
 impl<'a> UpdateMessageApiRequest<'a> {
-    pub async fn send(&self, client: &reqwest::Client) -> anyhow::Result<reqwest::Response> {
-        let bot_api_token = env::var("ZULIP_API_TOKEN").expect("ZULIP_API_TOKEN");
-
+    pub async fn send(&self, client: &GithubClient) -> anyhow::Result<reqwest::Response> {
         #[derive(serde::Serialize)]
         struct SerializedApi<'a> {
             #[serde(skip_serializing_if = "Option::is_none")]
@@ -501,22 +532,75 @@ impl<'a> UpdateMessageApiRequest<'a> {
             pub content: Option<&'a str>,
         }
 
-        Ok(client
+        let req = client
+            .raw()
             .patch(&format!(
-                "https://rust-lang.zulipchat.com/api/v1/messages/{}",
+                "{}/api/v1/messages/{}",
+                zulip_url(),
                 self.message_id
             ))
-            .basic_auth(BOT_EMAIL, Some(&bot_api_token))
             .form(&SerializedApi {
                 topic: self.topic,
                 propagate_mode: self.propagate_mode,
                 content: self.content,
-            })
-            .send()
-            .await?)
+            });
+        send_request(client, req).await
     }
 }
 
+#[tokio::test]
+async fn test_dry_run() {
+    // Requests which are sent fail, as there is no proxy on this port.
+    let http = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::all("http://127.0.0.1:1").unwrap())
+        .build()
+        .unwrap();
+    let client = GithubClient::new(http, String::from("token"));
+    let (result, planned) = github::dry_run(async {
+        let sent: MessageApiResponse = MessageApiRequest {
+            recipient: Recipient::Stream {
+                id: 1,
+                topic: "topic",
+            },
+            content: "hello",
+        }
+        .send(&client)
+        .await?
+        .json()
+        .await?;
+        UpdateMessageApiRequest {
+            message_id: sent.message_id,
+            topic: Some("renamed"),
+            propagate_mode: None,
+            content: None,
+        }
+        .send(&client)
+        .await?;
+        Ok::<_, anyhow::Error>(())
+    })
+    .await;
+    result.unwrap();
+    let planned = planned
+        .iter()
+        .map(|a| (a.method.as_str(), a.url.as_str(), a.body.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        planned,
+        [
+            (
+                "POST",
+                &*format!("{}/api/v1/messages", zulip_url()),
+                Some("type=stream&to=1&topic=topic&content=hello"),
+            ),
+            (
+                "PATCH",
+                &*format!("{}/api/v1/messages/0", zulip_url()),
+                Some("topic=renamed"),
+            ),
+        ]
+    );
+}
+
 async fn acknowledge(
     ctx: &Context,
     gh_id: i64,
@@ -711,18 +795,16 @@ struct AddReaction<'a> {
 }
 
 impl<'a> AddReaction<'a> {
-    pub async fn send(self, client: &reqwest::Client) -> anyhow::Result<reqwest::Response> {
-        let bot_api_token = env::var("ZULIP_API_TOKEN").expect("ZULIP_API_TOKEN");
-
-        Ok(client
+    pub async fn send(self, client: &GithubClient) -> anyhow::Result<reqwest::Response> {
+        let req = client
+            .raw()
             .post(&format!(
-                "https://rust-lang.zulipchat.com/api/v1/messages/{}/reactions",
+                "{}/api/v1/messages/{}/reactions",
+                zulip_url(),
                 self.message_id
             ))
-            .basic_auth(BOT_EMAIL, Some(&bot_api_token))
-            .form(&self)
-            .send()
-            .await?)
+            .form(&self);
+        send_request(client, req).await
     }
 }
 
@@ -767,7 +849,7 @@ async fn post_waiter(
         },
         content: waiting.primary,
     }
-    .send(ctx.github.raw())
+    .send(&ctx.github)
     .await?;
     let body = posted.text().await?;
     let message_id = serde_json::from_str::<SentMessage>(&body)
@@ -779,7 +861,7 @@ async fn post_waiter(
             message_id,
             emoji_name: reaction,
         }
-        .send(&ctx.github.raw())
+        .send(&ctx.github)
         .await
         .context("emoji reaction failed")?;
     }